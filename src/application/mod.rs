@@ -18,4 +18,11 @@ pub trait Application {
         now: SystemTime,
         tx: &mut Sender<ResMsg>,
     ) -> Box<dyn Iterator<Item = (SystemTime, Event)> + '_>;
+    /// Serialize this application's resumable progress (e.g. iteration count
+    /// and RNG state), to be stored alongside a
+    /// [`crate::storage_stack::StorageStack::snapshot`] taken at the same
+    /// point in time.
+    fn checkpoint(&self) -> Vec<u8>;
+    /// Resume progress from bytes produced by [`Self::checkpoint`].
+    fn restore_checkpoint(&mut self, data: &[u8]);
 }
@@ -7,10 +7,11 @@ use crossbeam::channel::Sender;
 use duration_str::deserialize_duration;
 use indicatif::{ProgressBar, ProgressStyle};
 use rand::{distributions::Uniform, prelude::Distribution, rngs::StdRng, Rng, SeedableRng};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use zipf::ZipfDistribution;
 
 use crate::{
+    histogram::Histogram,
     result_csv::{OpsInfo, ResMsg},
     Access, Block, Event,
 };
@@ -113,6 +114,15 @@ impl Default for DistConfig {
     }
 }
 
+/// Progress that needs to survive a [`crate::storage_stack::StorageStack`]
+/// checkpoint: which iteration a `BatchApp` is on and its RNG's exact
+/// position, not just the seed it was constructed with.
+#[derive(Serialize, Deserialize)]
+pub struct BatchAppCheckpoint {
+    cur_iteration: usize,
+    rng: StdRng,
+}
+
 /// Batch-oriented application with configurable access pattern.
 pub struct BatchApp {
     size: usize,
@@ -122,8 +132,8 @@ pub struct BatchApp {
     batch: usize,
     interval: Duration,
     rw: f64,
-    write_latency: Vec<Duration>,
-    read_latency: Vec<Duration>,
+    write_latency: Histogram,
+    read_latency: Histogram,
     iteration: usize,
     cur_iteration: usize,
     // Spinner
@@ -148,6 +158,22 @@ impl Dist {
 }
 
 impl BatchApp {
+    /// Capture the current iteration and RNG state, to be stored alongside a
+    /// [`crate::storage_stack::StorageStack::snapshot`] of the same point in
+    /// time.
+    pub fn checkpoint(&self) -> BatchAppCheckpoint {
+        BatchAppCheckpoint {
+            cur_iteration: self.cur_iteration,
+            rng: self.rng.clone(),
+        }
+    }
+
+    /// Resume a run from a [`BatchAppCheckpoint`] taken earlier in this batch.
+    pub fn restore_checkpoint(&mut self, checkpoint: BatchAppCheckpoint) {
+        self.cur_iteration = checkpoint.cur_iteration;
+        self.rng = checkpoint.rng;
+    }
+
     pub fn new(config: &BatchConfig) -> Self {
         assert!(config.size > 0);
         assert!(config.iteration > 0);
@@ -159,8 +185,8 @@ impl BatchApp {
             interval: config.interval,
             rw: config.rw,
             batch: config.batch,
-            write_latency: vec![],
-            read_latency: vec![],
+            write_latency: Histogram::new(),
+            read_latency: Histogram::new(),
             iteration: config.iteration,
             cur_iteration: 0,
             spinner: ProgressBar::new(config.iteration.try_into().unwrap()).with_style(
@@ -196,17 +222,33 @@ impl Application for BatchApp {
                 match a {
                     Access::Read(b) => Event::Cache(crate::cache::CacheMsg::Get(b)),
                     Access::Write(b) => Event::Cache(crate::cache::CacheMsg::Put(b)),
+                    Access::Discard(b) => Event::Cache(crate::cache::CacheMsg::Discard(b)),
                 },
             )
         }))
     }
 
+    fn checkpoint(&self) -> Vec<u8> {
+        bincode::serialize(&BatchApp::checkpoint(self)).expect("checkpoint must serialize")
+    }
+
+    fn restore_checkpoint(&mut self, data: &[u8]) {
+        let checkpoint: BatchAppCheckpoint =
+            bincode::deserialize(data).expect("checkpoint must deserialize");
+        BatchApp::restore_checkpoint(self, checkpoint);
+    }
+
     fn done(
         &mut self,
         access: Access,
         now: SystemTime,
         tx: &mut Sender<ResMsg>,
     ) -> Box<dyn Iterator<Item = (SystemTime, Event)> + '_> {
+        if let Access::Discard(_) = access {
+            // The batch workload never issues discards itself; nothing to
+            // track latency for.
+            return Box::new([].into_iter());
+        }
         let entry = self.current_reqs.get_mut(&access).unwrap();
         let when_issued = entry.0;
         entry.1 -= 1;
@@ -216,22 +258,23 @@ impl Application for BatchApp {
         let lat = match access {
             Access::Read(_) => &mut self.read_latency,
             Access::Write(_) => &mut self.write_latency,
+            Access::Discard(_) => unreachable!(),
         };
-        lat.push(now.duration_since(when_issued).expect("Negative Time"));
+        lat.record(now.duration_since(when_issued).expect("Negative Time"));
 
         if self.current_reqs.len() == 0 && self.cur_iteration + 1 < self.iteration {
             // END OF BATCH
             // TODO: Call Policy now, or do parallel messages (queue) to which a
             // policy can interject? Take oracle from Haura directly?
-            let mut writes = Vec::with_capacity(self.batch);
+            let mut writes = Histogram::new();
             std::mem::swap(&mut self.write_latency, &mut writes);
-            let mut reads = Vec::with_capacity(self.batch);
+            let mut reads = Histogram::new();
             std::mem::swap(&mut self.read_latency, &mut reads);
             tx.send(ResMsg::Application {
                 now,
                 interval: self.interval,
-                writes: OpsInfo { all: writes },
-                reads: OpsInfo { all: reads },
+                writes: OpsInfo { histogram: writes },
+                reads: OpsInfo { histogram: reads },
             })
             .unwrap();
             // println!(
@@ -9,14 +9,20 @@ use std::{
     error::Error,
     fs::OpenOptions,
     io::Write,
-    os::unix::fs::{FileExt, OpenOptionsExt},
+    os::unix::{
+        fs::{FileExt, OpenOptionsExt},
+        io::AsRawFd,
+    },
     path::PathBuf,
     process::ExitCode,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
     time::Duration,
 };
 
 /// TODO: Measure PMem with appropriate library
-/// TODO: Mutliple writers
 
 #[derive(Parser)]
 pub struct Options {
@@ -29,6 +35,12 @@ pub struct Options {
     sample_duration: String,
     #[arg(short, long, default_value_t = String::from("./result.csv"))]
     result_path: String,
+    /// Number of O_DIRECT requests to keep in flight at once (one worker
+    /// thread per unit of depth, each with its own share of the offsets), so a
+    /// device's peak bandwidth can be reached instead of measuring only
+    /// single-request latency.
+    #[arg(short = 'q', long, default_values_t = vec![1, 4, 16, 64, 128])]
+    queue_depths: Vec<usize>,
 }
 
 fn main() -> ExitCode {
@@ -50,7 +62,7 @@ fn faux_main() -> Result<(), Box<dyn Error>> {
         .create(true)
         .truncate(true)
         .open(opts.result_path)?;
-    let mut file = OpenOptions::new()
+    let file = OpenOptions::new()
         .write(true)
         .read(true)
         .create(true)
@@ -63,9 +75,9 @@ fn faux_main() -> Result<(), Box<dyn Error>> {
     // file.set_len(size as u64)?;
 
     results.write_fmt(format_args!(
-        "block_size,blocks,avg_latency_us,op,pattern\n"
+        "block_size,blocks,avg_latency_us,bandwidth_mibs,op,pattern,queue_depth\n"
     ))?;
-    for (op, block_size) in opts
+    for (op, block_size, queue_depth) in opts
         .block_sizes
         .iter()
         .map(|written| Byte::from_str(written).unwrap().get_bytes())
@@ -75,34 +87,48 @@ fn faux_main() -> Result<(), Box<dyn Error>> {
                 (Mode::SequentialRead, bs),
                 (Mode::RandomWrite, bs),
                 (Mode::RandomRead, bs),
+                (Mode::Discard, bs),
             ]
         })
+        .flat_map(|(op, bs)| opts.queue_depths.iter().map(move |qd| (op, bs, *qd)))
     {
         let blocks = size / block_size;
 
         println!(
-            "{}: Running benchmark with {} and {}",
+            "{}: Running benchmark with {}, {} and queue depth {}",
             "Prepared".bold(),
             format!("{}", HumanBytes(block_size as u64)).green(),
-            format!("{op}").bright_cyan()
+            format!("{op}").bright_cyan(),
+            queue_depth
         );
-        let (end, blocks) = run(
-            &mut file,
+        let (end, processed) = run(
+            &file,
             op,
             sample_duration,
             blocks.try_into().unwrap(),
             block_size.try_into().unwrap(),
+            queue_depth,
         )?;
-        let bw = (blocks as u128 * block_size) as f32 / 1024. / 1024. / end.as_secs_f32();
-        println!("{}: {op}: {} MiB/s", "Achieved".bold(), bw);
+        let bw = (processed as u128 * block_size) as f32 / 1024. / 1024. / end.as_secs_f32();
+        let iops = processed as f32 / end.as_secs_f32();
+        println!(
+            "{}: {op} (qd={queue_depth}): {} MiB/s, {} IOPS",
+            "Achieved".bold(),
+            bw,
+            iops
+        );
         println!("{}: {op}: {}s", "Achieved".bold(), end.as_secs_f32());
         results.write_fmt(format_args!(
-            "{},{},{},{},{}\n",
+            "{},{},{},{},{},{},{}\n",
             block_size,
-            blocks,
-            end.as_micros() / blocks as u128,
+            processed,
+            // Each worker issues its requests back to back, so per-request
+            // latency is elapsed time over what one worker completed.
+            end.as_micros() * queue_depth as u128 / processed.max(1) as u128,
+            bw,
             op.as_str_op(),
-            op.as_str_pattern()
+            op.as_str_pattern(),
+            queue_depth,
         ))?;
         std::thread::sleep(std::time::Duration::from_secs(5));
     }
@@ -115,6 +141,8 @@ enum Mode {
     RandomRead,
     SequentialWrite,
     SequentialRead,
+    /// Punch-hole/TRIM, issued at random offsets like a random write.
+    Discard,
 }
 
 impl Mode {
@@ -122,12 +150,13 @@ impl Mode {
         match self {
             Mode::RandomWrite | Mode::SequentialWrite => "write",
             Mode::RandomRead | Mode::SequentialRead => "read",
+            Mode::Discard => "discard",
         }
     }
 
     fn as_str_pattern(&self) -> &str {
         match self {
-            Mode::RandomWrite | Mode::RandomRead => "random",
+            Mode::RandomWrite | Mode::RandomRead | Mode::Discard => "random",
             Mode::SequentialRead | Mode::SequentialWrite => "sequential",
         }
     }
@@ -140,59 +169,98 @@ impl std::fmt::Display for Mode {
             Mode::RandomRead => f.write_str("Random Read"),
             Mode::SequentialWrite => f.write_str("Sequential Write"),
             Mode::SequentialRead => f.write_str("Sequential Read"),
+            Mode::Discard => f.write_str("Discard"),
         }
     }
 }
 
+/// Run `mode` against `map` for `run_until`, split across `queue_depth`
+/// worker threads (sequential modes give each worker a disjoint, interleaved
+/// slice of the offsets; random modes give each its own seeded random offset
+/// stream) that share the file through positional (pwrite/pread-style) accesses, so up to
+/// `queue_depth` O_DIRECT requests are in flight at once. Returns elapsed
+/// time and the number of accesses completed across all workers.
 fn run(
-    map: &mut std::fs::File,
+    map: &std::fs::File,
     mode: Mode,
     run_until: Duration,
     total_blocks: u64,
     block_size: usize,
+    queue_depth: usize,
 ) -> Result<(std::time::Duration, u64), std::io::Error> {
-    let buf_layout =
-        unsafe { std::alloc::Layout::from_size_align_unchecked(block_size, BLOCK_ALIGNMENT) };
-    let buf: *mut [u8] = unsafe {
-        std::ptr::slice_from_raw_parts_mut(std::alloc::alloc_zeroed(buf_layout), block_size)
-    };
-
-    let offsets: Box<dyn Iterator<Item = u64>>;
-    match mode {
-        Mode::RandomWrite | Mode::RandomRead => {
-            let rng = rand::rngs::StdRng::seed_from_u64(54321);
-            offsets = Box::new(
-                rng.sample_iter(rand::distributions::Uniform::new(0, total_blocks))
-                    .map(|x| x * block_size as u64),
-            )
-            // offsets.shuffle(&mut rng);
-        }
-        Mode::SequentialWrite | Mode::SequentialRead => {
-            offsets = Box::new((0..total_blocks).map(|x| x * block_size as u64));
-        }
-    }
-
-    let mut processed_blocks = 0;
+    let fd = map.as_raw_fd();
+    let processed = Arc::new(AtomicU64::new(0));
     let now = std::time::Instant::now();
-    unsafe {
-        for n in offsets {
-            match mode {
-                Mode::RandomWrite | Mode::SequentialWrite => {
-                    assert_eq!(map.write_at(&*buf, n)?, block_size);
-                }
-                Mode::RandomRead | Mode::SequentialRead => {
-                    assert_eq!(map.read_at(&mut *buf, n)?, block_size);
+
+    std::thread::scope(|scope| -> Result<(), std::io::Error> {
+        let mut handles = Vec::with_capacity(queue_depth);
+        for worker in 0..queue_depth {
+            let processed = Arc::clone(&processed);
+            handles.push(scope.spawn(move || -> Result<(), std::io::Error> {
+                let buf_layout = unsafe {
+                    std::alloc::Layout::from_size_align_unchecked(block_size, BLOCK_ALIGNMENT)
+                };
+                let buf: *mut [u8] = unsafe {
+                    std::ptr::slice_from_raw_parts_mut(
+                        std::alloc::alloc_zeroed(buf_layout),
+                        block_size,
+                    )
+                };
+
+                let offsets: Box<dyn Iterator<Item = u64>> = match mode {
+                    Mode::RandomWrite | Mode::RandomRead | Mode::Discard => {
+                        let rng = rand::rngs::StdRng::seed_from_u64(54321 + worker as u64);
+                        Box::new(
+                            rng.sample_iter(rand::distributions::Uniform::new(0, total_blocks))
+                                .map(|x| x * block_size as u64),
+                        )
+                    }
+                    Mode::SequentialWrite | Mode::SequentialRead => Box::new(
+                        (worker as u64..total_blocks)
+                            .step_by(queue_depth.max(1))
+                            .map(|x| x * block_size as u64),
+                    ),
+                };
+
+                unsafe {
+                    for n in offsets {
+                        match mode {
+                            Mode::RandomWrite | Mode::SequentialWrite => {
+                                assert_eq!(map.write_at(&*buf, n)?, block_size);
+                            }
+                            Mode::RandomRead | Mode::SequentialRead => {
+                                assert_eq!(map.read_at(&mut *buf, n)?, block_size);
+                            }
+                            Mode::Discard => {
+                                let ret = libc::fallocate(
+                                    fd,
+                                    libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+                                    n as libc::off_t,
+                                    block_size as libc::off_t,
+                                );
+                                if ret != 0 {
+                                    return Err(std::io::Error::last_os_error());
+                                }
+                            }
+                        }
+                        processed.fetch_add(1, Ordering::Relaxed);
+                        // FIXME: reduce costs
+                        // fetching takes around 100ns with comparisons, this might have a
+                        // rather large influence with 256b acccess taking only 250ns on
+                        // some NVM this might skew the result.
+                        if now.elapsed() > run_until {
+                            break;
+                        }
+                    }
                 }
-            }
-            processed_blocks += 1;
-            // FIXME: reduce costs
-            // fetching takes around 100ns with comparisons, this might have a
-            // rather large influence with 256b acccess taking only 250ns on
-            // some NVM this might skew the result.
-            if now.elapsed() > run_until {
-                break;
-            }
+                Ok(())
+            }));
         }
-    }
-    Ok((now.elapsed(), processed_blocks))
+        for handle in handles {
+            handle.join().expect("worker thread panicked")?;
+        }
+        Ok(())
+    })?;
+
+    Ok((now.elapsed(), processed.load(Ordering::Relaxed)))
 }
@@ -0,0 +1,253 @@
+use crate::{Block, Device};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashSet, VecDeque},
+    time::Duration,
+};
+
+use super::Cache;
+
+/// Adaptive Replacement Cache (Megiddo & Modha). Maintains two resident
+/// lists, `t1` (seen once) and `t2` (seen at least twice), each backed by a
+/// same-sized ghost list of evicted identifiers, `b1`/`b2`, that hold no
+/// data but let a later re-access adapt the T1/T2 split via the target `p`.
+#[derive(Serialize, Deserialize)]
+pub struct Arc {
+    t1: VecDeque<Block>,
+    t2: VecDeque<Block>,
+    b1: VecDeque<Block>,
+    b2: VecDeque<Block>,
+    /// Target size of `t1`, adapted on every ghost hit.
+    p: usize,
+    capacity: usize,
+    on_device: Device,
+    // Blocks with a pending write not yet reflected on the backing device.
+    dirty: HashSet<Block>,
+    /// Blocks that hit a ghost list and are waiting for the resulting fetch
+    /// to land in `put`, so it can be promoted straight into `t2` instead of
+    /// being treated as a cold miss.
+    pending_t2: HashSet<Block>,
+}
+
+impl Arc {
+    pub fn new(capacity: usize, dev: Device) -> Self {
+        Self {
+            t1: VecDeque::new(),
+            t2: VecDeque::new(),
+            b1: VecDeque::new(),
+            b2: VecDeque::new(),
+            p: 0,
+            capacity,
+            on_device: dev,
+            dirty: HashSet::new(),
+            pending_t2: HashSet::new(),
+        }
+    }
+
+    /// Adapt `p` and mark `block` for T2 promotion if it is sitting in a
+    /// ghost list. Idempotent via `pending_t2`, so it is safe to call from
+    /// both `get` (reads, which see the ghost hit directly) and `evict`
+    /// (writes, which only learn about it once eviction is attempted).
+    fn ghost_hit(&mut self, block: &Block) -> bool {
+        if self.pending_t2.contains(block) {
+            return true;
+        }
+        if self.b1.contains(block) {
+            let delta = if self.b1.len() >= self.b2.len() {
+                1
+            } else {
+                (self.b2.len() / self.b1.len().max(1)).max(1)
+            };
+            self.p = (self.p + delta).min(self.capacity);
+            self.pending_t2.insert(*block);
+            true
+        } else if self.b2.contains(block) {
+            let delta = if self.b2.len() >= self.b1.len() {
+                1
+            } else {
+                (self.b1.len() / self.b2.len().max(1)).max(1)
+            };
+            self.p = self.p.saturating_sub(delta);
+            self.pending_t2.insert(*block);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// REPLACE(x, p): evict from `t1` if it is non-empty and larger than the
+    /// target `p` (or ties with it while `x` is a `b2` ghost); otherwise `t2`
+    /// gives up its LRU entry instead.
+    fn replace(&mut self, incoming: &Block) -> Option<Block> {
+        if !self.t1.is_empty()
+            && (self.t1.len() > self.p
+                || (self.b2.contains(incoming) && self.t1.len() == self.p))
+        {
+            let evicted = self.t1.pop_back()?;
+            self.b1.push_front(evicted);
+            Some(evicted)
+        } else {
+            let evicted = self.t2.pop_back()?;
+            self.b2.push_front(evicted);
+            Some(evicted)
+        }
+    }
+}
+
+impl Cache for Arc {
+    fn get(&mut self, block: &Block) -> Option<Duration> {
+        if let Some(idx) = self.t1.iter().position(|b| b == block) {
+            let b = self.t1.remove(idx).unwrap();
+            self.t2.push_front(b);
+            return Some(Duration::ZERO);
+        }
+        if let Some(idx) = self.t2.iter().position(|b| b == block) {
+            let b = self.t2.remove(idx).unwrap();
+            self.t2.push_front(b);
+            return Some(Duration::ZERO);
+        }
+        self.ghost_hit(block);
+        None
+    }
+
+    fn put(&mut self, block: Block, dirty: bool) -> Duration {
+        // A write can land here with spare capacity and no preceding `get`,
+        // so unlike `evict` this is not guaranteed to have already run
+        // `ghost_hit` for `block` -- check `b1`/`b2` ourselves, or `p` would
+        // never adapt for a write-heavy workload that never misses.
+        if self.pending_t2.remove(&block) || self.ghost_hit(&block) {
+            self.pending_t2.remove(&block);
+            self.b1.retain(|b| b != &block);
+            self.b2.retain(|b| b != &block);
+            self.t2.push_front(block);
+        } else if let Some(idx) = self.t1.iter().position(|b| b == &block) {
+            let b = self.t1.remove(idx).unwrap();
+            self.t2.push_front(b);
+        } else if let Some(idx) = self.t2.iter().position(|b| b == &block) {
+            let b = self.t2.remove(idx).unwrap();
+            self.t2.push_front(b);
+        } else {
+            self.t1.push_front(block);
+        }
+        if dirty {
+            self.dirty.insert(block);
+        }
+        Duration::ZERO
+    }
+
+    fn clear(&mut self) -> Box<dyn Iterator<Item = Block>> {
+        let mut t1 = VecDeque::new();
+        let mut t2 = VecDeque::new();
+        std::mem::swap(&mut self.t1, &mut t1);
+        std::mem::swap(&mut self.t2, &mut t2);
+        self.b1.clear();
+        self.b2.clear();
+        self.p = 0;
+        self.dirty.clear();
+        self.pending_t2.clear();
+        Box::new(t1.into_iter().chain(t2))
+    }
+
+    fn evict(&mut self, incoming: &Block) -> Option<Block> {
+        // A ghost hit already accounts for `p`; its REPLACE still runs, but
+        // the case-IV list-size bookkeeping below only applies to a
+        // genuine cold miss.
+        if !self.ghost_hit(incoming) {
+            let l1 = self.t1.len() + self.b1.len();
+            if l1 == self.capacity {
+                if self.t1.len() < self.capacity {
+                    self.b1.pop_back();
+                } else {
+                    let evicted = self.t1.pop_back();
+                    return evicted;
+                }
+            } else if l1 < self.capacity
+                && l1 + self.t2.len() + self.b2.len() >= self.capacity
+            {
+                if l1 + self.t2.len() + self.b2.len() == 2 * self.capacity {
+                    self.b2.pop_back();
+                }
+            }
+        }
+        self.replace(incoming)
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn len(&self) -> usize {
+        self.t1.len() + self.t2.len()
+    }
+
+    fn dirty(&self, block: &Block) -> bool {
+        self.dirty.contains(block)
+    }
+
+    fn dirty_count(&self) -> usize {
+        self.dirty.len()
+    }
+
+    fn mark_clean(&mut self, block: &Block) {
+        self.dirty.remove(block);
+    }
+
+    fn flush(&mut self) -> Box<dyn Iterator<Item = Block>> {
+        let mut tmp = HashSet::new();
+        std::mem::swap(&mut self.dirty, &mut tmp);
+        Box::new(tmp.into_iter())
+    }
+
+    fn discard(&mut self, block: &Block) {
+        self.t1.retain(|b| b != block);
+        self.t2.retain(|b| b != block);
+        self.b1.retain(|b| b != block);
+        self.b2.retain(|b| b != block);
+        self.dirty.remove(block);
+        self.pending_t2.remove(block);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage_stack::{to_device, DeviceLatencyTable};
+
+    fn test_device() -> Device {
+        let mut loaded = std::collections::HashMap::new();
+        loaded.insert("test".to_owned(), DeviceLatencyTable::default());
+        to_device("test", &loaded, 0).unwrap()
+    }
+
+    #[test]
+    fn put_adapts_p_on_ghost_hit_without_a_preceding_get() {
+        // Fill t1/t2 to capacity and force Block(2) out into b1, mirroring
+        // the trace that exposed the missing `ghost_hit` call in `put`.
+        let mut cache = Arc::new(3, test_device());
+        cache.put(Block(1), false); // t1 = [1]
+        cache.put(Block(2), false); // t1 = [2, 1]
+        cache.get(&Block(1)); // t1 = [2], t2 = [1]
+        cache.put(Block(3), false); // t1 = [3, 2], t2 = [1]
+        cache.evict(&Block(4)); // evicts Block(2) from t1 into b1
+
+        assert_eq!(cache.b1, VecDeque::from([Block(2)]));
+        assert_eq!(cache.p, 0);
+
+        // A write to a ghost-listed block with no preceding `get`/`evict` for
+        // it must still adapt `p` and promote straight into `t2`.
+        cache.put(Block(2), false);
+
+        assert_eq!(cache.p, 1);
+        assert_eq!(cache.t2.front(), Some(&Block(2)));
+        assert!(cache.b1.is_empty());
+        assert_eq!(cache.t1, VecDeque::from([Block(3)]));
+    }
+
+    #[test]
+    fn put_on_a_cold_block_goes_to_t1() {
+        let mut cache = Arc::new(3, test_device());
+        cache.put(Block(1), false);
+        assert_eq!(cache.t1, VecDeque::from([Block(1)]));
+        assert!(cache.t2.is_empty());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::{storage_stack::DeviceAccessParams, Block, Device};
+use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashSet, VecDeque},
     time::Duration,
@@ -6,12 +7,14 @@ use std::{
 
 use super::Cache;
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct Fifo {
     blocks: HashSet<Block>,
     queue: VecDeque<Block>,
     on_device: Device,
     capacity: usize,
+    // Blocks with a pending write not yet reflected on the backing device.
+    dirty: HashSet<Block>,
 }
 
 impl Fifo {
@@ -21,6 +24,7 @@ impl Fifo {
             queue: VecDeque::default(),
             on_device: dev,
             capacity,
+            dirty: HashSet::default(),
         }
     }
 }
@@ -30,11 +34,14 @@ impl Cache for Fifo {
         self.blocks.get(block).map(|_| Duration::ZERO)
     }
 
-    fn put(&mut self, block: Block) -> Duration {
+    fn put(&mut self, block: Block, dirty: bool) -> Duration {
         if !self.blocks.contains(&block) {
             self.queue.push_front(block.clone());
             self.blocks.insert(block);
         }
+        if dirty {
+            self.dirty.insert(block);
+        }
         Duration::ZERO
     }
 
@@ -42,10 +49,11 @@ impl Cache for Fifo {
         let mut tmp = HashSet::new();
         std::mem::swap(&mut self.blocks, &mut tmp);
         self.queue.clear();
+        self.dirty.clear();
         Box::new(tmp.into_iter())
     }
 
-    fn evict(&mut self) -> Option<Block> {
+    fn evict(&mut self, _incoming: &Block) -> Option<Block> {
         self.queue.pop_back().map(|b| {
             self.blocks.remove(&b);
             b
@@ -59,4 +67,29 @@ impl Cache for Fifo {
     fn len(&self) -> usize {
         self.queue.len()
     }
+
+    fn dirty(&self, block: &Block) -> bool {
+        self.dirty.contains(block)
+    }
+
+    fn dirty_count(&self) -> usize {
+        self.dirty.len()
+    }
+
+    fn mark_clean(&mut self, block: &Block) {
+        self.dirty.remove(block);
+    }
+
+    fn flush(&mut self) -> Box<dyn Iterator<Item = Block>> {
+        let mut tmp = HashSet::new();
+        std::mem::swap(&mut self.dirty, &mut tmp);
+        Box::new(tmp.into_iter())
+    }
+
+    fn discard(&mut self, block: &Block) {
+        if self.blocks.remove(block) {
+            self.queue.retain(|b| b != block);
+        }
+        self.dirty.remove(block);
+    }
 }
@@ -1,56 +1,161 @@
 use crate::{storage_stack::DeviceAccessParams, Block, Device};
-use std::{collections::VecDeque, time::Duration};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 
 use super::Cache;
 
+/// Slab index into `Lru::nodes`, standing in for a linked-list pointer: a
+/// plain index rather than an unsafe raw pointer, so `get`/`put`/`evict` stay
+/// O(1) (move one node to the list head) without giving up safe Rust, unlike
+/// the O(n) `VecDeque::iter().position(...)` scan this replaces.
+type NodeIdx = usize;
+
+#[derive(Serialize, Deserialize)]
+struct Node {
+    block: Block,
+    prev: Option<NodeIdx>,
+    next: Option<NodeIdx>,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Lru {
-    entries: VecDeque<Block>,
+    nodes: Vec<Node>,
+    index: HashMap<Block, NodeIdx>,
+    /// Most-recently-used end of the list.
+    head: Option<NodeIdx>,
+    /// Least-recently-used end of the list; the next eviction victim.
+    tail: Option<NodeIdx>,
+    /// Slots freed by a removed node, reused before growing `nodes`.
+    free: Vec<NodeIdx>,
     capacity: usize,
     on_device: Device,
+    // Blocks with a pending write not yet reflected on the backing device.
+    dirty: HashSet<Block>,
 }
 
 impl Lru {
     pub fn new(capacity: usize, dev: Device) -> Self {
         Self {
-            entries: VecDeque::new(),
+            nodes: Vec::new(),
+            index: HashMap::new(),
+            head: None,
+            tail: None,
+            free: Vec::new(),
             capacity,
             on_device: dev,
+            dirty: HashSet::new(),
         }
     }
+
+    /// Unlink `idx` from the list, leaving the node itself in `self.nodes`.
+    fn unlink(&mut self, idx: NodeIdx) {
+        let (prev, next) = (self.nodes[idx].prev, self.nodes[idx].next);
+        match prev {
+            Some(p) => self.nodes[p].next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(n) => self.nodes[n].prev = prev,
+            None => self.tail = prev,
+        }
+    }
+
+    /// Make `idx` the new MRU head.
+    fn push_front(&mut self, idx: NodeIdx) {
+        self.nodes[idx].prev = None;
+        self.nodes[idx].next = self.head;
+        if let Some(h) = self.head {
+            self.nodes[h].prev = Some(idx);
+        }
+        self.head = Some(idx);
+        if self.tail.is_none() {
+            self.tail = Some(idx);
+        }
+    }
+
+    /// Move an already-linked node to the front, e.g. on a cache hit.
+    fn touch(&mut self, idx: NodeIdx) {
+        if self.head == Some(idx) {
+            return;
+        }
+        self.unlink(idx);
+        self.push_front(idx);
+    }
+
+    /// Insert `block` as a fresh MRU entry, reusing a freed slot if one
+    /// exists instead of always growing `nodes`.
+    fn insert_front(&mut self, block: Block) -> NodeIdx {
+        let idx = match self.free.pop() {
+            Some(idx) => {
+                self.nodes[idx] = Node {
+                    block,
+                    prev: None,
+                    next: None,
+                };
+                idx
+            }
+            None => {
+                self.nodes.push(Node {
+                    block,
+                    prev: None,
+                    next: None,
+                });
+                self.nodes.len() - 1
+            }
+        };
+        self.push_front(idx);
+        self.index.insert(block, idx);
+        idx
+    }
+
+    /// Fully remove `idx`'s entry: unlink it, drop its index entry, and
+    /// recycle its slot.
+    fn remove_node(&mut self, idx: NodeIdx) {
+        self.unlink(idx);
+        self.index.remove(&self.nodes[idx].block);
+        self.free.push(idx);
+    }
 }
 
 impl Cache for Lru {
     fn get(&mut self, block: &Block) -> Option<Duration> {
-        if let Some(idx) = self
-            .entries
-            .iter()
-            .enumerate()
-            .find(|x| x.1 == block)
-            .map(|x| x.0)
-        {
-            assert_eq!(self.entries.remove(idx).as_ref(), Some(block));
-            self.entries.push_front(block.to_owned());
-            Some(Duration::ZERO)
-        } else {
-            None
-        }
+        let idx = *self.index.get(block)?;
+        self.touch(idx);
+        Some(Duration::ZERO)
     }
 
-    fn put(&mut self, block: Block) -> Duration {
-        if self.get(&block).is_none() {
-            self.entries.push_front(block);
+    fn put(&mut self, block: Block, dirty: bool) -> Duration {
+        match self.index.get(&block) {
+            Some(&idx) => self.touch(idx),
+            None => {
+                self.insert_front(block);
+            }
+        }
+        if dirty {
+            self.dirty.insert(block);
         }
         Duration::ZERO
     }
 
     fn clear(&mut self) -> Box<dyn Iterator<Item = Block>> {
-        let mut tmp = VecDeque::new();
-        std::mem::swap(&mut self.entries, &mut tmp);
-        Box::new(tmp.into_iter())
+        let blocks = self.index.keys().copied().collect::<Vec<_>>();
+        self.nodes.clear();
+        self.index.clear();
+        self.head = None;
+        self.tail = None;
+        self.free.clear();
+        self.dirty.clear();
+        Box::new(blocks.into_iter())
     }
 
-    fn evict(&mut self) -> Option<Block> {
-        self.entries.pop_back()
+    fn evict(&mut self, _incoming: &Block) -> Option<Block> {
+        let idx = self.tail?;
+        let block = self.nodes[idx].block;
+        self.remove_node(idx);
+        Some(block)
     }
 
     fn capacity(&self) -> usize {
@@ -58,6 +163,31 @@ impl Cache for Lru {
     }
 
     fn len(&self) -> usize {
-        self.entries.len()
+        self.index.len()
+    }
+
+    fn dirty(&self, block: &Block) -> bool {
+        self.dirty.contains(block)
+    }
+
+    fn dirty_count(&self) -> usize {
+        self.dirty.len()
+    }
+
+    fn mark_clean(&mut self, block: &Block) {
+        self.dirty.remove(block);
+    }
+
+    fn flush(&mut self) -> Box<dyn Iterator<Item = Block>> {
+        let mut tmp = HashSet::new();
+        std::mem::swap(&mut self.dirty, &mut tmp);
+        Box::new(tmp.into_iter())
+    }
+
+    fn discard(&mut self, block: &Block) {
+        if let Some(&idx) = self.index.get(block) {
+            self.remove_node(idx);
+        }
+        self.dirty.remove(block);
     }
 }
@@ -3,16 +3,20 @@ use std::{
     time::{Duration, SystemTime},
 };
 
-use crate::Access;
+use serde::{Deserialize, Serialize};
+
+use crate::{storage_stack::BLOCK_SIZE_IN_B, Access};
 
 /// This module contains a simple cache trait.
 /// Implementations for simple policies are provided.
 /// Extension can be done by implementing the trait on a new struct. No actual data is stored.
 use super::{Block, Event};
 
+mod arc;
 mod fifo;
 mod lru;
 mod noop;
+pub use arc::Arc;
 pub use fifo::Fifo;
 pub use lru::Lru;
 pub use noop::Noop;
@@ -20,33 +24,286 @@ pub use noop::Noop;
 pub trait Cache {
     /// Check whether the cache contains a given block.
     fn get(&mut self, block: &Block) -> Option<Duration>;
-    /// Insert a new entry to cache.
-    fn put(&mut self, block: Block) -> Duration;
+    /// Insert a new entry to cache. `dirty` marks it as holding a write not
+    /// yet reflected on the backing device (a read fetched into cache is not
+    /// dirty; an application write is).
+    fn put(&mut self, block: Block, dirty: bool) -> Duration;
     /// Removes all elements in the cache an returns an iterator over contained elements.
     fn clear(&mut self) -> Box<dyn Iterator<Item = Block>>;
-    /// Evict the next entry.
-    fn evict(&mut self) -> Option<Block>;
+    /// Evict an entry to make room for `incoming`. Policies whose eviction
+    /// choice depends on the incoming key (e.g. ARC's ghost-list lookup) can
+    /// inspect it; simpler policies ignore it.
+    fn evict(&mut self, incoming: &Block) -> Option<Block>;
     /// Return the total capacity.
     fn capacity(&self) -> usize;
     /// Return the number of current entries.
     fn len(&self) -> usize;
+    /// Whether `block` has a write pending write-back to the backing device.
+    fn dirty(&self, block: &Block) -> bool;
+    /// Number of currently dirty blocks, for threshold-triggered flushing.
+    fn dirty_count(&self) -> usize;
+    /// Mark a dirtied block's write-back as complete.
+    fn mark_clean(&mut self, block: &Block);
+    /// Drain every currently dirty block, marking them all clean in the
+    /// process, for an explicit cache flush.
+    fn flush(&mut self) -> Box<dyn Iterator<Item = Block>>;
+    /// Drop `block` outright, discarding any pending dirty write-back — used
+    /// when the backing data is about to be trimmed and no longer needs to
+    /// survive an eviction.
+    fn discard(&mut self, block: &Block);
+}
+
+/// Selects how a cache handles writes: [`CacheWriteMode::WriteBack`] defers
+/// the device write until the entry is evicted or explicitly flushed,
+/// [`CacheWriteMode::WriteThrough`] pays the device write immediately and
+/// completes the application request only once it lands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CacheWriteMode {
+    #[default]
+    WriteBack,
+    WriteThrough,
 }
 
 // Meta logic for caches, takes cares of size requirements and interdependencies of caches
+#[derive(Serialize, Deserialize)]
 pub struct CacheLogic {
     in_eviction: HashSet<Block>,
     in_fetch: HashSet<Block>,
-    cache: Box<dyn Cache>,
+    cache: CacheKind,
+    mode: CacheWriteMode,
     queue_eviction: VecDeque<CacheMsg>,
     queue_completion: VecDeque<CacheMsg>,
+    /// Dirty-to-capacity ratio above which a `Put` proactively triggers a
+    /// flush instead of waiting for an explicit `CacheMsg::Flush`.
+    dirty_ratio: Option<f64>,
+    /// Blocks currently being written back as part of a flush, so their
+    /// `WriteFinished` can be told apart from an ordinary eviction write.
+    flushing: HashSet<Block>,
+    /// Present iff evicted blocks are demoted to a faster spill tier rather
+    /// than dropped outright.
+    spill: Option<SpillTier>,
+}
+
+/// Configuration for a cache's optional spill tier, loaded alongside the
+/// rest of the cache definition. Fixed latencies rather than a real
+/// device's latency table, matching [`crate::storage_stack::Codec`]'s
+/// style for a modeled-but-not-queued cost.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct SpillConfig {
+    /// Fraction of the fast tier's capacity held in reserve: once occupancy
+    /// reaches `capacity * (1 - reserved_ratio)`, the next eviction spills
+    /// instead of just discarding the evictee.
+    pub reserved_ratio: f64,
+    #[serde(deserialize_with = "duration_str::deserialize_duration")]
+    pub write_latency: Duration,
+    #[serde(deserialize_with = "duration_str::deserialize_duration")]
+    pub read_latency: Duration,
+}
+
+/// A faster fallback tier an eviction can demote a block to instead of
+/// simply dropping it, e.g. a local SSD sitting between an in-memory cache
+/// and a slow backing device. A later `Get` that misses the fast tier but
+/// hits here is still cheaper than a real backing-device read, and
+/// re-promotes the block on the way out.
+#[derive(Serialize, Deserialize)]
+struct SpillTier {
+    /// Fraction of the fast tier's capacity held in reserve: once occupancy
+    /// reaches `capacity * (1 - reserved_ratio)`, the next eviction spills
+    /// instead of just discarding the evictee.
+    reserved_ratio: f64,
+    /// Aligned/bulk transfer cost charged per spilled block — a single
+    /// large sequential transfer, cheaper than the backing device's random
+    /// I/O cost.
+    write_latency: Duration,
+    /// Cost to read a block back out of the spill tier on a hit.
+    read_latency: Duration,
+    blocks: HashSet<Block>,
+    bytes_written: usize,
+    read_backs: usize,
+    hits: usize,
+    misses: usize,
+}
+
+/// Lifetime spill-tier counters, reported once at the end of a run; all
+/// zero if no spill tier is configured.
+#[derive(Default)]
+pub struct SpillStats {
+    pub bytes_written: usize,
+    pub read_backs: usize,
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl SpillTier {
+    fn new(config: &SpillConfig) -> Self {
+        Self {
+            reserved_ratio: config.reserved_ratio,
+            write_latency: config.write_latency,
+            read_latency: config.read_latency,
+            blocks: HashSet::new(),
+            bytes_written: 0,
+            read_backs: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Whether an eviction happening while the fast tier holds `len` entries
+    /// out of `capacity` should spill rather than just discard.
+    fn should_spill(&self, len: usize, capacity: usize) -> bool {
+        capacity > 0 && len as f64 >= capacity as f64 * (1.0 - self.reserved_ratio)
+    }
+
+    /// Demote `block` into the spill tier, returning the write latency to
+    /// charge for it.
+    fn spill(&mut self, block: Block) -> Duration {
+        if self.blocks.insert(block) {
+            self.bytes_written += BLOCK_SIZE_IN_B;
+        }
+        self.write_latency
+    }
+
+    /// Look up `block`, promoting it out of the spill tier on a hit.
+    fn get(&mut self, block: &Block) -> Option<Duration> {
+        if self.blocks.remove(block) {
+            self.hits += 1;
+            self.read_backs += 1;
+            Some(self.read_latency)
+        } else {
+            self.misses += 1;
+            None
+        }
+    }
+
+    fn discard(&mut self, block: &Block) {
+        self.blocks.remove(block);
+    }
+}
+
+/// Enumerates the concrete [`Cache`] implementations so `CacheLogic` can be
+/// checkpointed without relying on trait-object serialization.
+#[derive(Serialize, Deserialize)]
+pub enum CacheKind {
+    Fifo(Fifo),
+    Lru(Lru),
+    Noop(Noop),
+    Arc(Arc),
+}
+
+impl Cache for CacheKind {
+    fn get(&mut self, block: &Block) -> Option<Duration> {
+        match self {
+            CacheKind::Fifo(c) => c.get(block),
+            CacheKind::Lru(c) => c.get(block),
+            CacheKind::Noop(c) => c.get(block),
+            CacheKind::Arc(c) => c.get(block),
+        }
+    }
+
+    fn put(&mut self, block: Block, dirty: bool) -> Duration {
+        match self {
+            CacheKind::Fifo(c) => c.put(block, dirty),
+            CacheKind::Lru(c) => c.put(block, dirty),
+            CacheKind::Noop(c) => c.put(block, dirty),
+            CacheKind::Arc(c) => c.put(block, dirty),
+        }
+    }
+
+    fn clear(&mut self) -> Box<dyn Iterator<Item = Block>> {
+        match self {
+            CacheKind::Fifo(c) => c.clear(),
+            CacheKind::Lru(c) => c.clear(),
+            CacheKind::Noop(c) => c.clear(),
+            CacheKind::Arc(c) => c.clear(),
+        }
+    }
+
+    fn evict(&mut self, incoming: &Block) -> Option<Block> {
+        match self {
+            CacheKind::Fifo(c) => c.evict(incoming),
+            CacheKind::Lru(c) => c.evict(incoming),
+            CacheKind::Noop(c) => c.evict(incoming),
+            CacheKind::Arc(c) => c.evict(incoming),
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        match self {
+            CacheKind::Fifo(c) => c.capacity(),
+            CacheKind::Lru(c) => c.capacity(),
+            CacheKind::Noop(c) => c.capacity(),
+            CacheKind::Arc(c) => c.capacity(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            CacheKind::Fifo(c) => c.len(),
+            CacheKind::Lru(c) => c.len(),
+            CacheKind::Noop(c) => c.len(),
+            CacheKind::Arc(c) => c.len(),
+        }
+    }
+
+    fn dirty(&self, block: &Block) -> bool {
+        match self {
+            CacheKind::Fifo(c) => c.dirty(block),
+            CacheKind::Lru(c) => c.dirty(block),
+            CacheKind::Noop(c) => c.dirty(block),
+            CacheKind::Arc(c) => c.dirty(block),
+        }
+    }
+
+    fn dirty_count(&self) -> usize {
+        match self {
+            CacheKind::Fifo(c) => c.dirty_count(),
+            CacheKind::Lru(c) => c.dirty_count(),
+            CacheKind::Noop(c) => c.dirty_count(),
+            CacheKind::Arc(c) => c.dirty_count(),
+        }
+    }
+
+    fn mark_clean(&mut self, block: &Block) {
+        match self {
+            CacheKind::Fifo(c) => c.mark_clean(block),
+            CacheKind::Lru(c) => c.mark_clean(block),
+            CacheKind::Noop(c) => c.mark_clean(block),
+            CacheKind::Arc(c) => c.mark_clean(block),
+        }
+    }
+
+    fn flush(&mut self) -> Box<dyn Iterator<Item = Block>> {
+        match self {
+            CacheKind::Fifo(c) => c.flush(),
+            CacheKind::Lru(c) => c.flush(),
+            CacheKind::Noop(c) => c.flush(),
+            CacheKind::Arc(c) => c.flush(),
+        }
+    }
+
+    fn discard(&mut self, block: &Block) {
+        match self {
+            CacheKind::Fifo(c) => c.discard(block),
+            CacheKind::Lru(c) => c.discard(block),
+            CacheKind::Noop(c) => c.discard(block),
+            CacheKind::Arc(c) => c.discard(block),
+        }
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum CacheMsg {
     Get(Block),
     Put(Block),
     ReadFinished(Block),
     WriteFinished(Block),
+    /// Explicit request to write back every currently dirty block.
+    Flush,
+    /// A flush-triggered write-back of a single block has landed.
+    FlushFinished(Block),
+    /// Trim/punch-hole a block: drop it from the cache without writing it
+    /// back, then free its capacity on the backing device.
+    Discard(Block),
 }
 
 impl CacheMsg {
@@ -70,18 +327,110 @@ impl CacheMsg {
             CacheMsg::Put(b) => *b,
             CacheMsg::ReadFinished(b) => *b,
             CacheMsg::WriteFinished(b) => *b,
+            CacheMsg::FlushFinished(b) => *b,
+            CacheMsg::Discard(b) => *b,
+            CacheMsg::Flush => unimplemented!(),
         }
     }
 }
 
 impl CacheLogic {
-    pub fn new(cache: Box<dyn Cache>) -> Self {
+    pub fn new(
+        cache: CacheKind,
+        mode: CacheWriteMode,
+        dirty_ratio: Option<f64>,
+        spill: Option<&SpillConfig>,
+    ) -> Self {
         Self {
             in_eviction: Default::default(),
             in_fetch: Default::default(),
             cache,
+            mode,
             queue_eviction: Default::default(),
             queue_completion: Default::default(),
+            dirty_ratio,
+            flushing: Default::default(),
+            spill: spill.map(SpillTier::new),
+        }
+    }
+
+    /// Lifetime spill-tier counters, for end-of-run reporting.
+    pub fn spill_stats(&self) -> SpillStats {
+        match &self.spill {
+            Some(s) => SpillStats {
+                bytes_written: s.bytes_written,
+                read_backs: s.read_backs,
+                hits: s.hits,
+                misses: s.misses,
+            },
+            None => SpillStats::default(),
+        }
+    }
+
+    /// Drain every dirty block as a write-back, as triggered by either an
+    /// explicit `CacheMsg::Flush` or crossing `dirty_ratio`.
+    fn flush_dirty(&mut self, now: SystemTime) -> Box<dyn Iterator<Item = (SystemTime, Event)> + '_> {
+        let blocks = self.cache.flush().collect::<Vec<_>>();
+        for b in &blocks {
+            self.flushing.insert(*b);
+        }
+        Box::new(
+            blocks
+                .into_iter()
+                .map(move |b| (now, Event::Storage(Access::Write(b)))),
+        )
+    }
+
+    /// Evict one entry to make room for `pending`, re-queuing `pending` for
+    /// completion once its slot is actually free. A dirty evictee has to be
+    /// written back to the device first; a clean one frees its slot
+    /// immediately since there is nothing to persist.
+    fn evict_one(
+        &mut self,
+        pending: CacheMsg,
+        now: SystemTime,
+    ) -> Box<dyn Iterator<Item = (SystemTime, Event)> + '_> {
+        if self.cache.capacity() == 0 {
+            let access = match &pending {
+                CacheMsg::Get(b) => Access::Read(*b),
+                CacheMsg::Put(b) => Access::Write(*b),
+                _ => unreachable!(),
+            };
+            return Box::new([(now, Event::Storage(access))].into_iter());
+        }
+        let incoming = pending.block();
+        self.queue_eviction.push_back(pending);
+        let len_before_eviction = self.cache.len();
+        let capacity = self.cache.capacity();
+        match self.cache.evict(&incoming) {
+            Some(evicted) => {
+                // A spilling evictee keeps a copy on the faster spill
+                // device in parallel with (not instead of) any real
+                // backing-device write-back below, so a later re-request
+                // can hit the spill tier rather than the slow backing
+                // store.
+                if self
+                    .spill
+                    .as_ref()
+                    .is_some_and(|s| s.should_spill(len_before_eviction, capacity))
+                {
+                    self.spill.as_mut().unwrap().spill(evicted);
+                }
+                if self.cache.dirty(&evicted) {
+                    self.in_eviction.insert(evicted);
+                    Box::new([(now, Event::Storage(Access::Write(evicted)))].into_iter())
+                } else {
+                    Box::new(
+                        [self
+                            .queue_eviction
+                            .pop_front()
+                            .map(|m| (now, Event::Cache(m)))]
+                        .into_iter()
+                        .filter_map(|e| e),
+                    )
+                }
+            }
+            None => Box::new([].into_iter()),
         }
     }
 
@@ -117,19 +466,23 @@ impl CacheLogic {
                     if self.cache.len() + self.in_eviction.len() + self.in_fetch.len() + 1
                         > self.cache.capacity()
                     {
-                        self.queue_eviction.push_back(msg);
-                        if let Some(evicted) = self.cache.evict() {
-                            // evict entry and wait for completion
-                            self.in_eviction.insert(evicted);
-                            Box::new([(now, Event::Storage(Access::Write(evicted)))].into_iter())
-                        } else {
-                            if self.cache.capacity() == 0 {
-                                return Box::new(
-                                    [(now, Event::Storage(Access::Read(block)))].into_iter(),
-                                );
-                            }
-                            Box::new([].into_iter())
-                        }
+                        self.evict_one(msg, now)
+                    } else if let Some(dur) = self.spill.as_mut().and_then(|s| s.get(&block)) {
+                        // Spill hit: cheaper than a real device read, and
+                        // re-promotes the block back into the fast tier.
+                        self.cache.put(block, false);
+                        Box::new(
+                            [(now + dur, Event::Application(Access::Read(block)))]
+                                .into_iter()
+                                .chain(
+                                    [self
+                                        .queue_eviction
+                                        .pop_front()
+                                        .map(|m| (now, Event::Cache(m)))]
+                                    .into_iter()
+                                    .filter_map(|e| e),
+                                ),
+                        )
                     } else {
                         // Fetch block from storage
                         self.queue_completion.push_back(msg);
@@ -143,21 +496,34 @@ impl CacheLogic {
                 if self.cache.len() + self.in_eviction.len() + self.in_fetch.len() + 1
                     > self.cache.capacity()
                 {
-                    self.queue_eviction.push_back(msg);
-                    if let Some(evicted) = self.cache.evict() {
-                        // evict entry and wait for completion
-                        self.in_eviction.insert(evicted);
-                        Box::new([(now, Event::Storage(Access::Write(evicted)))].into_iter())
+                    self.evict_one(msg, now)
+                } else {
+                    let dur = self.cache.put(block, true);
+                    // A dirty ratio above the configured threshold
+                    // proactively writes back every dirty block, rather
+                    // than waiting for an explicit `Flush`.
+                    let over_threshold = self.dirty_ratio.is_some_and(|ratio| {
+                        self.cache.capacity() > 0
+                            && self.cache.dirty_count() as f64 / self.cache.capacity() as f64
+                                >= ratio
+                    });
+                    let flush_evs = if over_threshold {
+                        self.flush_dirty(now).collect::<Vec<_>>()
                     } else {
-                        if self.cache.capacity() == 0 {
-                            return Box::new(
-                                [(now, Event::Storage(Access::Write(block)))].into_iter(),
-                            );
-                        }
-                        Box::new([].into_iter())
+                        Vec::new()
+                    };
+
+                    if self.mode == CacheWriteMode::WriteThrough {
+                        // Writethrough pays the device write immediately;
+                        // the application is only told it's done once that
+                        // write lands, via `WriteFinished` below.
+                        self.queue_completion.push_back(CacheMsg::Put(block));
+                        return Box::new(
+                            [(now, Event::Storage(Access::Write(block)))]
+                                .into_iter()
+                                .chain(flush_evs),
+                        );
                     }
-                } else {
-                    let dur = self.cache.put(block);
 
                     return Box::new(
                         [(now + dur, Event::Application(Access::Write(block)))]
@@ -169,7 +535,8 @@ impl CacheLogic {
                                     .map(|m| (now, Event::Cache(m)))]
                                 .into_iter()
                                 .filter_map(|e| e),
-                            ),
+                            )
+                            .chain(flush_evs),
                     );
                 }
             }
@@ -178,7 +545,7 @@ impl CacheLogic {
                     return Box::new([(now, Event::Application(Access::Read(block)))].into_iter());
                 }
                 self.in_fetch.remove(&block);
-                self.cache.put(block);
+                self.cache.put(block, false);
                 assert!(self.cache.len() <= self.cache.capacity());
                 let evs = self
                     .queue_completion
@@ -205,14 +572,59 @@ impl CacheLogic {
                 if self.cache.capacity() == 0 {
                     return Box::new([(now, Event::Application(Access::Write(block)))].into_iter());
                 }
+                self.cache.mark_clean(&block);
+                self.in_eviction.remove(&block);
+                let flush_finished = self
+                    .flushing
+                    .remove(&block)
+                    .then_some((now, Event::Cache(CacheMsg::FlushFinished(block))));
+                // A writethrough put is waiting on this very write to tell
+                // the application it's done.
+                let evs = self
+                    .queue_completion
+                    .iter()
+                    .filter(|m| m.is_put())
+                    .filter(move |m| m.block() == block)
+                    .map(move |m| (now, Event::Application(Access::Write(m.block()))))
+                    .collect::<Vec<_>>();
+                self.queue_completion
+                    .retain(|m| !m.is_put() || m.block() != block);
+                Box::new(
+                    evs.into_iter()
+                        .chain(
+                            [self
+                                .queue_eviction
+                                .pop_front()
+                                .map(|m| (now, Event::Cache(m)))]
+                            .into_iter()
+                            .filter_map(|e| e),
+                        )
+                        .chain(flush_finished),
+                )
+            }
+            CacheMsg::Flush => self.flush_dirty(now),
+            CacheMsg::FlushFinished(_) => Box::new([].into_iter()),
+            CacheMsg::Discard(block) => {
+                self.cache.discard(&block);
+                self.in_fetch.remove(&block);
                 self.in_eviction.remove(&block);
+                self.flushing.remove(&block);
+                if let Some(s) = self.spill.as_mut() {
+                    s.discard(&block);
+                }
+                self.queue_completion
+                    .retain(|m| (m.is_get() || m.is_put()) && m.block() != block);
                 Box::new(
-                    [self
-                        .queue_eviction
-                        .pop_front()
-                        .map(|m| (now, Event::Cache(m)))]
-                    .into_iter()
-                    .filter_map(|e| e),
+                    [(now, Event::Storage(Access::Discard(block)))]
+                        .into_iter()
+                        .chain(
+                            [self
+                                .queue_eviction
+                                .pop_front()
+                                .map(|m| (now, Event::Cache(m)))]
+                            .into_iter()
+                            .filter_map(|e| e),
+                        ),
                 )
             }
         }
@@ -233,7 +645,7 @@ mod tests {
 
     #[test]
     fn get_special_direct() {
-        let mut cache = CacheLogic::new(Box::new(Noop {}));
+        let mut cache = CacheLogic::new(CacheKind::Noop(Noop {}), CacheWriteMode::default(), None, None);
         assert_eq!(
             cache
                 .process(CacheMsg::Get(Block(1)), SystemTime::UNIX_EPOCH)
@@ -254,7 +666,7 @@ mod tests {
 
     #[test]
     fn put_special_direct() {
-        let mut cache = CacheLogic::new(Box::new(Noop {}));
+        let mut cache = CacheLogic::new(CacheKind::Noop(Noop {}), CacheWriteMode::default(), None, None);
         assert_eq!(
             cache
                 .process(CacheMsg::Put(Block(1)), SystemTime::UNIX_EPOCH)
@@ -1,7 +1,10 @@
 use std::time::Duration;
 
+use serde::{Deserialize, Serialize};
+
 use super::Cache;
 
+#[derive(Serialize, Deserialize)]
 pub struct Noop {}
 
 impl Cache for Noop {
@@ -9,7 +12,7 @@ impl Cache for Noop {
         None
     }
 
-    fn put(&mut self, _block: crate::Block) -> std::time::Duration {
+    fn put(&mut self, _block: crate::Block, _dirty: bool) -> std::time::Duration {
         Duration::ZERO
     }
 
@@ -17,7 +20,7 @@ impl Cache for Noop {
         Box::new([].into_iter())
     }
 
-    fn evict(&mut self) -> Option<crate::Block> {
+    fn evict(&mut self, _incoming: &crate::Block) -> Option<crate::Block> {
         None
     }
 
@@ -28,4 +31,20 @@ impl Cache for Noop {
     fn len(&self) -> usize {
         0
     }
+
+    fn dirty(&self, _block: &crate::Block) -> bool {
+        false
+    }
+
+    fn dirty_count(&self) -> usize {
+        0
+    }
+
+    fn mark_clean(&mut self, _block: &crate::Block) {}
+
+    fn flush(&mut self) -> Box<dyn Iterator<Item = crate::Block>> {
+        Box::new([].into_iter())
+    }
+
+    fn discard(&mut self, _block: &crate::Block) {}
 }
@@ -1,13 +1,17 @@
 use crate::{
     application::{Application, BatchApp, BatchConfig},
-    cache::{Cache, CacheLogic, Fifo, Lru, Noop},
+    cache::{Arc, CacheKind, CacheLogic, CacheWriteMode, Fifo, Lru, Noop, SpillConfig},
     placement::PlacementConfig,
-    storage_stack::{to_device, DeviceLatencyTable, DeviceState, DiskId},
+    storage_stack::{
+        to_device, Codec, DeviceLatencyTable, DeviceQueue, DeviceState, DiskId, QueueScheduler,
+        RateLimiter, RateLimiterConfig, ThinProvisioning,
+    },
     Block, SimError,
 };
 
+use duration_str::deserialize_duration;
 use serde::Deserialize;
-use std::collections::{HashMap, VecDeque};
+use std::{collections::HashMap, time::Duration};
 use strum::EnumIter;
 
 #[derive(Deserialize)]
@@ -30,21 +34,38 @@ impl Config {
         loaded_devices: &HashMap<String, DeviceLatencyTable>,
     ) -> Result<HashMap<DiskId, DeviceState>, SimError> {
         let mut map = HashMap::new();
-        for (id, (_name, dev)) in self.devices.iter().enumerate() {
+        for (id, (name, dev)) in self.devices.iter().enumerate() {
             map.insert(
                 DiskId(id),
                 DeviceState {
+                    name: name.clone(),
                     kind: to_device(&dev.kind, loaded_devices, dev.capacity)?,
                     free: dev.capacity,
                     total: dev.capacity,
-                    reserved_until: std::time::UNIX_EPOCH,
-                    submission_queue: VecDeque::new(),
-                    max_queue_len: 128,
-                    total_q: std::time::Duration::ZERO,
-                    total_req: 0,
-                    max_q: std::time::Duration::ZERO,
-                    idle_time: std::time::Duration::ZERO,
-                    current_queue_len: 0,
+                    thin: dev
+                        .thin
+                        .map(|t| ThinProvisioning::new(t.physical_capacity, t.alloc_latency)),
+                    queues: (0..dev.queue_count.unwrap_or(1))
+                        .map(|_| DeviceQueue::new(128, std::time::UNIX_EPOCH))
+                        .collect(),
+                    scheduler: dev.scheduler.build(),
+                    in_flight: HashMap::new(),
+                    rate_limiter: dev
+                        .rate_limiter
+                        .map(|c| RateLimiter::new(&c, std::time::UNIX_EPOCH)),
+                    compressing: dev.compressing,
+                    ssd: dev.ssd,
+                    stale: 0,
+                    codec: dev.codec.map(|c| Codec {
+                        ratio: c.ratio,
+                        encode_latency: c.encode_latency,
+                        decode_latency: c.decode_latency,
+                    }),
+                    codec_latency_total: Duration::ZERO,
+                    health: 0.0,
+                    footprint_carry: 0.0,
+                    codec_footprint_carry: 0.0,
+                    resident_footprint: HashMap::new(),
                 },
             );
         }
@@ -55,10 +76,15 @@ impl Config {
         &self,
         loaded_devices: &HashMap<String, DeviceLatencyTable>,
     ) -> Result<CacheLogic, SimError> {
-        Ok(CacheLogic::new(match &self.cache {
-            Some(c) => c.build(loaded_devices)?,
-            None => Box::new(Noop {}),
-        }))
+        Ok(CacheLogic::new(
+            match &self.cache {
+                Some(c) => c.build(loaded_devices)?,
+                None => CacheKind::Noop(Noop {}),
+            },
+            self.cache.as_ref().map(|c| c.mode).unwrap_or_default(),
+            self.cache.as_ref().and_then(|c| c.dirty_ratio),
+            self.cache.as_ref().and_then(|c| c.spill.as_ref()),
+        ))
     }
 }
 
@@ -80,6 +106,71 @@ impl App {
 pub struct DeviceConfig {
     kind: String,
     capacity: usize,
+    rate_limiter: Option<RateLimiterConfig>,
+    /// Number of independent request queues to model on this device, e.g. one
+    /// per multi-queue NVMe submission/completion pair. Defaults to a single
+    /// queue, matching prior single-queue behavior.
+    queue_count: Option<usize>,
+    /// Which of the device's queues the next access is routed to. Defaults
+    /// to least-loaded, matching prior behavior.
+    #[serde(default)]
+    scheduler: QueueSchedulerConfig,
+    /// Over-commits `capacity`: present iff this device is thin-provisioned,
+    /// i.e. backed by less physical space than it advertises.
+    thin: Option<ThinProvisioningConfig>,
+    /// Present iff this tier compresses data it stores, e.g. a cold tier
+    /// trading capacity for CPU/latency. The value is unused here and only
+    /// marks the tier as compressing; the actual per-block ratio is sampled
+    /// by the placement policy.
+    compressing: Option<f32>,
+    /// Marks this device as flash/SSD-class, subject to GC write
+    /// amplification as migrations leave stale space behind.
+    #[serde(default)]
+    ssd: bool,
+    /// Present iff the storage stack itself compresses/decompresses every
+    /// access to this device, e.g. a cold tier backed by a real codec. Unlike
+    /// `compressing`, this ratio and its CPU cost are actually applied.
+    codec: Option<CodecConfig>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct CodecConfig {
+    /// Fraction of a block's original size retained after compression.
+    ratio: f32,
+    #[serde(deserialize_with = "deserialize_duration")]
+    encode_latency: Duration,
+    #[serde(deserialize_with = "deserialize_duration")]
+    decode_latency: Duration,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub enum QueueSchedulerConfig {
+    #[default]
+    LeastLoaded,
+    RoundRobin,
+    /// Each queue's in-flight count is scaled down by its `weights` entry
+    /// before comparison, so a higher-weighted queue is treated as less busy.
+    WeightedDeadline { weights: Vec<f64> },
+}
+
+impl QueueSchedulerConfig {
+    pub fn build(&self) -> QueueScheduler {
+        match self {
+            QueueSchedulerConfig::LeastLoaded => QueueScheduler::LeastLoaded,
+            QueueSchedulerConfig::RoundRobin => QueueScheduler::RoundRobin { next: 0 },
+            QueueSchedulerConfig::WeightedDeadline { weights } => QueueScheduler::WeightedDeadline {
+                weights: weights.clone(),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct ThinProvisioningConfig {
+    /// Real backing size, distinct from the advertised `DeviceConfig::capacity`.
+    physical_capacity: usize,
+    #[serde(deserialize_with = "deserialize_duration")]
+    alloc_latency: Duration,
 }
 
 #[derive(Deserialize)]
@@ -87,6 +178,16 @@ pub struct CacheConfig {
     algorithm: CacheAlgorithm,
     device: String,
     capacity: usize,
+    #[serde(default)]
+    mode: CacheWriteMode,
+    /// Fraction of `capacity` that may be dirty before a `Put` proactively
+    /// flushes every dirty block, rather than waiting for an explicit
+    /// flush request. `None` disables threshold-triggered flushing.
+    #[serde(default)]
+    dirty_ratio: Option<f64>,
+    /// Present iff evicted blocks should be demoted to a faster spill tier
+    /// instead of being dropped outright.
+    spill: Option<SpillConfig>,
 }
 
 #[derive(Deserialize, PartialEq, Eq)]
@@ -94,23 +195,28 @@ pub enum CacheAlgorithm {
     Lru,
     Fifo,
     Noop,
+    Arc,
 }
 
 impl CacheConfig {
     pub fn build(
         &self,
         loaded_devices: &HashMap<String, DeviceLatencyTable>,
-    ) -> Result<Box<dyn Cache>, SimError> {
+    ) -> Result<CacheKind, SimError> {
         match self.algorithm {
-            CacheAlgorithm::Lru => Ok(Box::new(Lru::new(
+            CacheAlgorithm::Lru => Ok(CacheKind::Lru(Lru::new(
+                self.capacity,
+                to_device(&self.device, loaded_devices, self.capacity)?,
+            ))),
+            CacheAlgorithm::Fifo => Ok(CacheKind::Fifo(Fifo::new(
                 self.capacity,
                 to_device(&self.device, loaded_devices, self.capacity)?,
             ))),
-            CacheAlgorithm::Fifo => Ok(Box::new(Fifo::new(
+            CacheAlgorithm::Noop => Ok(CacheKind::Noop(Noop {})),
+            CacheAlgorithm::Arc => Ok(CacheKind::Arc(Arc::new(
                 self.capacity,
                 to_device(&self.device, loaded_devices, self.capacity)?,
             ))),
-            CacheAlgorithm::Noop => Ok(Box::new(Noop {})),
         }
     }
 }
@@ -0,0 +1,184 @@
+use std::time::Duration;
+
+/// Number of linear sub-buckets per binary magnitude (must be a power of
+/// two); bounds the relative error of any quantile read back out to
+/// `1 / SUB_BUCKETS`, independent of how large the recorded values get.
+const LOG2_SUB_BUCKETS: u32 = 6;
+const SUB_BUCKETS: u64 = 1 << LOG2_SUB_BUCKETS;
+/// `value_ns` is a `u64`, so its magnitude never exceeds 64.
+const MAGNITUDES: usize = 65;
+
+/// A log-linear, HdrHistogram-style latency histogram: `record` is O(1) and
+/// memory is bounded (`MAGNITUDES * SUB_BUCKETS` counters) regardless of how
+/// many samples or how wide a range of values are recorded, unlike keeping
+/// every sample in a `Vec` and sorting it per query.
+#[derive(Clone)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    total: u64,
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Histogram {
+            counts: vec![0; MAGNITUDES << LOG2_SUB_BUCKETS],
+            total: 0,
+        }
+    }
+
+    /// Bucket index for `value_ns`: the top bits select the binary magnitude
+    /// (`exponent = 64 - leading_zeros(value)`), the next `LOG2_SUB_BUCKETS`
+    /// bits linearly subdivide that magnitude.
+    fn bucket_of(value_ns: u64) -> usize {
+        let exponent = 64 - value_ns.leading_zeros() as u64;
+        let sub = if exponent == 0 {
+            0
+        } else {
+            let lower = 1u64 << (exponent - 1);
+            let range = lower;
+            (((value_ns - lower) as u128 * SUB_BUCKETS as u128) / range as u128) as u64
+        };
+        ((exponent << LOG2_SUB_BUCKETS) + sub) as usize
+    }
+
+    /// Representative value of `bucket`: its lower bound plus half its width.
+    fn value_of(bucket: usize) -> Duration {
+        let exponent = (bucket as u64) >> LOG2_SUB_BUCKETS;
+        if exponent == 0 {
+            return Duration::ZERO;
+        }
+        let sub = (bucket as u64) & (SUB_BUCKETS - 1);
+        let lower = 1u64 << (exponent - 1);
+        let range = lower;
+        // Undo `bucket_of`'s scaling exactly, so magnitudes narrower than
+        // `SUB_BUCKETS` nanoseconds also map back inside their own bucket.
+        let offset = ((sub as u128 * range as u128) / SUB_BUCKETS as u128) as u64;
+        Duration::from_nanos(lower + offset + range / (2 * SUB_BUCKETS))
+    }
+
+    pub fn record(&mut self, value: Duration) {
+        let ns = value.as_nanos().min(u64::MAX as u128) as u64;
+        self.counts[Self::bucket_of(ns)] += 1;
+        self.total += 1;
+    }
+
+    /// Combine `other`'s counts into `self`, as when rolling per-interval
+    /// histograms up into a run-wide one.
+    pub fn merge(&mut self, other: &Histogram) {
+        for (a, b) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *a += b;
+        }
+        self.total += other.total;
+    }
+
+    pub fn len(&self) -> u64 {
+        self.total
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+
+    /// The value at percentile `p` (e.g. `0.99` for p99), approximated to
+    /// within `1 / SUB_BUCKETS` of the true quantile.
+    pub fn quantile(&self, p: f64) -> Duration {
+        if self.total == 0 {
+            return Duration::ZERO;
+        }
+        let target = ((p * self.total as f64).ceil() as u64).clamp(1, self.total);
+        let mut cumulative = 0u64;
+        for (bucket, &count) in self.counts.iter().enumerate() {
+            cumulative += count;
+            if cumulative >= target {
+                return Self::value_of(bucket);
+            }
+        }
+        unreachable!("cumulative count must reach total by the last bucket");
+    }
+
+    pub fn max(&self) -> Duration {
+        self.counts
+            .iter()
+            .rposition(|&count| count > 0)
+            .map(Self::value_of)
+            .unwrap_or(Duration::ZERO)
+    }
+
+    pub fn avg(&self) -> Duration {
+        if self.total == 0 {
+            return Duration::ZERO;
+        }
+        let sum_ns: u128 = self
+            .counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(bucket, &count)| Self::value_of(bucket).as_nanos() * count as u128)
+            .sum();
+        Duration::from_nanos((sum_ns / self.total as u128) as u64)
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_histogram_reports_zero() {
+        let h = Histogram::new();
+        assert!(h.is_empty());
+        assert_eq!(h.len(), 0);
+        assert_eq!(h.max(), Duration::ZERO);
+        assert_eq!(h.avg(), Duration::ZERO);
+        assert_eq!(h.quantile(0.99), Duration::ZERO);
+    }
+
+    #[test]
+    fn quantile_and_max_track_recorded_values() {
+        let mut h = Histogram::new();
+        for ns in [100, 200, 300, 400, 1_000_000] {
+            h.record(Duration::from_nanos(ns));
+        }
+        assert_eq!(h.len(), 5);
+        assert!(!h.is_empty());
+        // The max-valued sample dominates, approximated within the
+        // documented 1/SUB_BUCKETS relative-error bound of its bucket.
+        let max = h.max();
+        let rel_error = (max.as_nanos() as f64 - 1_000_000.0).abs() / 1_000_000.0;
+        assert!(rel_error <= 1.0 / SUB_BUCKETS as f64, "max={max:?}");
+        // p100 (the highest recorded sample) must land in the same bucket as `max`.
+        assert_eq!(h.quantile(1.0), max);
+    }
+
+    #[test]
+    fn merge_combines_counts_and_totals() {
+        let mut a = Histogram::new();
+        a.record(Duration::from_nanos(100));
+        let mut b = Histogram::new();
+        b.record(Duration::from_nanos(100));
+        b.record(Duration::from_nanos(200));
+
+        a.merge(&b);
+
+        assert_eq!(a.len(), 3);
+        assert_eq!(a.quantile(1.0), b.quantile(1.0));
+    }
+
+    #[test]
+    fn bucket_of_is_monotonic_in_value() {
+        // Coarser magnitudes must never sort below finer ones, or `quantile`
+        // could return a smaller value for a larger percentile.
+        let mut prev = Histogram::bucket_of(0);
+        for ns in [1, 2, 7, 63, 64, 65, 1_000, 1_000_000, u64::MAX] {
+            let bucket = Histogram::bucket_of(ns);
+            assert!(bucket >= prev, "bucket_of({ns}) = {bucket} regressed below {prev}");
+            prev = bucket;
+        }
+    }
+}
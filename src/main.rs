@@ -28,6 +28,7 @@ use indicatif::HumanBytes;
 use placement::{PlacementMsg, PlacementPolicy};
 use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
 use result_csv::ResMsg;
+use serde::{Deserialize, Serialize};
 use storage_stack::{StorageError, StorageMsg, StorageStack};
 use strum::IntoEnumIterator;
 use thiserror::Error;
@@ -35,23 +36,26 @@ use thiserror::Error;
 use crate::{
     cache::CacheMsg,
     config::App,
-    storage_stack::{load_devices, Device, DeviceSer},
+    storage_stack::{load_devices, load_raw_profiles, Device, DeviceSer},
 };
 
 mod application;
 mod cache;
 mod config;
+mod histogram;
 mod placement;
 mod result_csv;
 mod storage_stack;
 
-#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub struct Block(usize);
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Access {
     Read(Block),
     Write(Block),
+    /// Trim/punch-hole: the block's contents no longer need to be preserved.
+    Discard(Block),
 }
 
 impl Access {
@@ -59,6 +63,7 @@ impl Access {
         match self {
             Access::Read(_) => true,
             Access::Write(_) => false,
+            Access::Discard(_) => false,
         }
     }
 
@@ -66,6 +71,7 @@ impl Access {
         match self {
             Access::Read(ref block) => block,
             Access::Write(ref block) => block,
+            Access::Discard(ref block) => block,
         }
     }
 }
@@ -92,6 +98,15 @@ pub struct PolicySimulator<S> {
         std::thread::JoinHandle<Result<(), std::io::Error>>,
         Sender<ResMsg>,
     ),
+    /// `true` iff `stack` was restored from a checkpoint rather than built
+    /// fresh, so `run` knows block placement is already in place and skips
+    /// `prepare`'s random initial distribution.
+    resumed: bool,
+    /// Where to write a final [`StorageStack::snapshot`] (plus a sibling
+    /// `.app` file holding `application.checkpoint()`) once the run
+    /// completes, so it can be branched into further runs from the same
+    /// point. Absent unless `--checkpoint` was given.
+    checkpoint_path: Option<PathBuf>,
 }
 
 impl<S> PolicySimulator<S> {
@@ -135,8 +150,13 @@ impl<S> PolicySimulator<S> {
     }
 
     /// Execute the main event digestion.
-    fn run(mut self) -> Result<(), SimError> {
-        self.prepare();
+    fn run(mut self) -> Result<(), SimError>
+    where
+        S: Serialize,
+    {
+        if !self.resumed {
+            self.prepare();
+        }
         // Start the application
         for (time, ev) in self
             .application
@@ -148,10 +168,12 @@ impl<S> PolicySimulator<S> {
         }
 
         // Startup migration policy
-        for ev in self
-            .policy
-            .init(&self.stack.devices, &self.stack.blocks, self.now)
-        {
+        for ev in self.policy.init(
+            &self.stack.devices,
+            &self.stack.blocks,
+            &mut self.stack.subscriptions,
+            self.now,
+        ) {
             self.insert_event(ev.0, ev.1)
         }
         while let Some((then, event)) = self.events.pop_first() {
@@ -194,6 +216,11 @@ impl<S> PolicySimulator<S> {
             };
         }
 
+        if let Some(path) = &self.checkpoint_path {
+            self.stack.snapshot(path)?;
+            std::fs::write(path.with_extension("app"), self.application.checkpoint())?;
+        }
+
         {
             let total_runtime = self.now.duration_since(std::time::UNIX_EPOCH).unwrap();
             self.results_td
@@ -206,6 +233,16 @@ impl<S> PolicySimulator<S> {
                 .1
                 .send(ResMsg::Device { map, total_runtime })
                 .unwrap();
+            let spill = self.stack.cache.spill_stats();
+            self.results_td
+                .1
+                .send(ResMsg::Cache {
+                    bytes_written: spill.bytes_written,
+                    read_backs: spill.read_backs,
+                    hits: spill.hits,
+                    misses: spill.misses,
+                })
+                .unwrap();
             self.results_td.1.send(ResMsg::Done).unwrap();
             self.results_td.0.join().unwrap()?;
         }
@@ -233,6 +270,11 @@ pub enum SimError {
     },
     #[error("Custom device \"{0}\" was not found in given path.")]
     MissingCustomDevice(String),
+    #[error("Could not snapshot or restore simulation state: {source}")]
+    Checkpoint {
+        #[from]
+        source: bincode::Error,
+    },
     #[error("An error occured: {0}.")]
     Generic(String),
     #[error("An error occured: {source}")]
@@ -248,6 +290,11 @@ struct SimCli {
     cmd: Commands,
     #[arg(short, long, default_value_t = String::from("./additional_devices"))]
     add_device_path: String,
+    /// Directory of raw `profile-device` benchmark results (measured
+    /// latency/bandwidth, no curve-fitting), merged in alongside
+    /// `add_device_path`'s pre-fitted devices.
+    #[arg(long)]
+    raw_profile_path: Option<String>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -260,6 +307,16 @@ pub enum Commands {
     Sim {
         #[arg(id = "CONFIG_PATH")]
         config: PathBuf,
+        /// Write a `StorageStack::snapshot` (plus a sibling `.app` file with
+        /// the application's progress) once the run completes, so it can be
+        /// branched into further runs with `--resume` from the same point.
+        #[arg(long)]
+        checkpoint: Option<PathBuf>,
+        /// Resume from a checkpoint written by `--checkpoint`, restoring
+        /// block placement, cache state and application progress instead of
+        /// starting from a freshly-prepared stack.
+        #[arg(long)]
+        resume: Option<PathBuf>,
     },
 }
 
@@ -271,6 +328,19 @@ fn main() -> ExitCode {
     ExitCode::SUCCESS
 }
 
+/// Load `add_device_path`'s pre-fitted devices and, if given, merge in
+/// `raw_profile_path`'s measured `profile-device` results.
+fn load_all_devices(
+    add_device_path: &str,
+    raw_profile_path: &Option<String>,
+) -> Result<HashMap<String, storage_stack::DeviceLatencyTable>, SimError> {
+    let mut devices = load_devices(add_device_path)?;
+    if let Some(raw_profile_path) = raw_profile_path {
+        devices.extend(load_raw_profiles(raw_profile_path)?);
+    }
+    Ok(devices)
+}
+
 fn faux_main() -> Result<(), SimError> {
     let args = SimCli::parse();
 
@@ -281,7 +351,7 @@ fn faux_main() -> Result<(), SimError> {
             for dev in DeviceSer::iter() {
                 println!("\t{dev:?}",);
             }
-            for (id, dev) in load_devices(&args.add_device_path)?.iter() {
+            for (id, dev) in load_all_devices(&args.add_device_path, &args.raw_profile_path)?.iter() {
                 println!(
                     "\t{id} (block sizes: {:?})",
                     dev.keys()
@@ -298,12 +368,16 @@ fn faux_main() -> Result<(), SimError> {
             }
             Ok(())
         }
-        Commands::Sim { config } => {
+        Commands::Sim {
+            config,
+            checkpoint,
+            resume,
+        } => {
             let mut file = std::fs::OpenOptions::new().read(true).open(config)?;
             let mut content = String::new();
             file.read_to_string(&mut content)?;
             let config: config::Config = toml::from_str(&content)?;
-            let custom_devices = load_devices(&args.add_device_path)?;
+            let custom_devices = load_all_devices(&args.add_device_path, &args.raw_profile_path)?;
             // append suffix to avoid overwriting data
             let mut cur = 0;
             let mut results = config
@@ -328,22 +402,46 @@ fn faux_main() -> Result<(), SimError> {
             }
             std::fs::create_dir_all(&results).unwrap();
 
-            let sim: PolicySimulator<()> = PolicySimulator {
-                stack: StorageStack {
+            let stack = match &resume {
+                Some(path) => StorageStack::restore(path, &custom_devices)?,
+                None => StorageStack {
                     blocks: [].into(),
                     devices: config.devices(&custom_devices)?,
                     state: (),
                     cache: config.cache(&custom_devices)?,
                     blocks_on_hold: Default::default(),
+                    subscriptions: Default::default(),
                 },
+            };
+            let mut application = config.app.build();
+            if let Some(path) = &resume {
+                let data = std::fs::read(path.with_extension("app"))?;
+                application.restore_checkpoint(&data);
+            }
+
+            let mut sim: PolicySimulator<()> = PolicySimulator {
+                stack,
                 policy: config.placement.build(),
-                application: config.app.build(),
+                application,
                 now: std::time::UNIX_EPOCH,
                 events: BTreeMap::new(),
                 rng: rand::rngs::StdRng::seed_from_u64(1234),
                 results_td: result_csv::ResultCollector::new(results)
                     .map(|(coll, tx)| (std::thread::spawn(|| coll.main()), tx))?,
+                resumed: resume.is_some(),
+                checkpoint_path: checkpoint,
             };
+            // Fast-forward the policy's own bookkeeping from its
+            // write-ahead journal, if it was configured with one, so a
+            // resumed run doesn't have to recompute placement decisions
+            // from scratch.
+            if resume.is_some() {
+                if let Some(journal_path) = config.placement.journal_path() {
+                    let file = std::fs::File::open(journal_path)?;
+                    let records = placement::JournalReader::new(file).replay();
+                    sim.policy.replay(&records);
+                }
+            }
             sim.run()
         }
     }
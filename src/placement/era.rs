@@ -0,0 +1,360 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, SystemTime},
+};
+
+use crossbeam::channel::Sender;
+use fixedbitset::FixedBitSet;
+use priority_queue::DoublePriorityQueue;
+
+use crate::{
+    result_csv::{MovementInfo, ResMsg},
+    storage_stack::{DeviceAccessParams, DeviceState, DiskId, BLOCK_SIZE_IN_B},
+    Block, Event,
+};
+
+use super::{PlacementMsg, PlacementPolicy};
+
+/// Recency tracked in discrete "eras" instead of a decaying counter.
+///
+/// Every `Migrate` interval advances `current_era` by one. Each touched
+/// block is stamped with `last_touched_era` and also flips a bit in the
+/// writeset for the era it was touched in; writesets are kept only for eras
+/// inside the demotion window. `migrate()` does not read the writesets:
+/// aging cold blocks scans `last_touched_era` for every known block on each
+/// pass. This copes better with bursty-then-idle workloads than a
+/// `p += 1` / `p *= (1 - decay)` scheme, where a single burst long ago can
+/// keep outweighing a block that has gone cold since.
+pub struct EraPolicy {
+    blocks: HashMap<DiskId, DoublePriorityQueue<Block, u32>>,
+    block_ordinal: HashMap<Block, usize>,
+    last_touched_era: HashMap<Block, u32>,
+    idle_disks: HashMap<DiskId, Duration>,
+    /// Writesets for eras still inside the demotion window, oldest first.
+    /// The back of the deque is always the writeset for `current_era`.
+    writesets: VecDeque<FixedBitSet>,
+    current_era: u32,
+    /// Blocks not touched for more eras than this are demotion candidates.
+    cold_eras_threshold: u32,
+    reactiveness: usize,
+    interval: Duration,
+    /// Per-block compressibility ratio (fraction of its size retained after
+    /// compression), sampled once at `init` from `[compress_min, compress_max]`.
+    /// Only matters for blocks that end up on a compressing tier.
+    compressibility: HashMap<Block, f32>,
+    compress_min: f32,
+    compress_max: f32,
+    /// Decompression throughput, in bytes/s, charged as an extra read cost
+    /// when promoting a block off a compressing tier.
+    decompression_bandwidth: f64,
+    /// Floor on how often `migrate()` actually does work: a call before
+    /// `last_pass + min_delay` has elapsed re-arms the next attempt but
+    /// skips the re-evaluation itself.
+    min_delay: Duration,
+    last_pass: SystemTime,
+}
+
+impl EraPolicy {
+    pub fn new(
+        interval: Duration,
+        reactiveness: usize,
+        cold_eras_threshold: u32,
+        compress_min: f32,
+        compress_max: f32,
+        decompression_bandwidth: f64,
+        min_delay: Duration,
+    ) -> Self {
+        EraPolicy {
+            blocks: HashMap::new(),
+            block_ordinal: HashMap::new(),
+            last_touched_era: HashMap::new(),
+            idle_disks: HashMap::new(),
+            writesets: VecDeque::new(),
+            current_era: 0,
+            cold_eras_threshold,
+            reactiveness,
+            interval,
+            compressibility: HashMap::new(),
+            compress_min,
+            compress_max,
+            decompression_bandwidth,
+            min_delay,
+            last_pass: SystemTime::UNIX_EPOCH,
+        }
+    }
+
+    /// Extra read cost from decompressing a block with the given
+    /// compressibility `ratio` back out, proportional to its compressed size.
+    fn decompression_cost(&self, ratio: f32) -> Duration {
+        Duration::from_secs_f64(BLOCK_SIZE_IN_B as f64 * ratio as f64 / self.decompression_bandwidth)
+    }
+}
+
+impl PlacementPolicy for EraPolicy {
+    fn init(
+        &mut self,
+        devices: &HashMap<DiskId, DeviceState>,
+        blocks: &HashMap<Block, DiskId>,
+        _subs: &mut crate::storage_stack::SubscriptionManager,
+        now: SystemTime,
+    ) -> Box<dyn Iterator<Item = (SystemTime, Event)>> {
+        for dev in devices {
+            self.blocks
+                .insert(dev.0.clone(), DoublePriorityQueue::new());
+            self.idle_disks.insert(dev.0.clone(), Duration::ZERO);
+        }
+        self.writesets
+            .push_back(FixedBitSet::with_capacity(blocks.len()));
+        for (ordinal, block) in blocks.keys().enumerate() {
+            self.block_ordinal.insert(block.clone(), ordinal);
+            self.last_touched_era.insert(block.clone(), 0);
+        }
+        let mut rng = rand::thread_rng();
+        for block in blocks {
+            self.blocks.get_mut(block.1).unwrap().push(block.0.clone(), 0);
+            self.compressibility.insert(
+                block.0.clone(),
+                rand::Rng::gen_range(&mut rng, self.compress_min..=self.compress_max),
+            );
+        }
+        Box::new(
+            [(
+                now + self.interval,
+                Event::PlacementPolicy(PlacementMsg::Migrate),
+            )]
+            .into_iter(),
+        )
+    }
+
+    fn update(
+        &mut self,
+        msg: PlacementMsg,
+        devices: &mut HashMap<DiskId, DeviceState>,
+        blocks: &HashMap<Block, DiskId>,
+        now: SystemTime,
+        tx: &mut Sender<ResMsg>,
+    ) -> Box<dyn Iterator<Item = (SystemTime, Event)>> {
+        match msg {
+            PlacementMsg::Migrate => return self.migrate(devices, blocks, now, tx),
+            _ => {}
+        }
+        let block = msg.block();
+        let dev = blocks.get(block).unwrap();
+        self.last_touched_era.insert(block.clone(), self.current_era);
+        if let Some(&ordinal) = self.block_ordinal.get(block) {
+            self.writesets.back_mut().unwrap().insert(ordinal);
+        }
+        self.blocks
+            .get_mut(dev)
+            .unwrap()
+            .change_priority(block, self.current_era);
+        Box::new([].into_iter())
+    }
+
+    fn migrate(
+        &mut self,
+        devices: &mut HashMap<DiskId, DeviceState>,
+        _blocks: &HashMap<Block, DiskId>,
+        now: SystemTime,
+        tx: &mut Sender<ResMsg>,
+    ) -> Box<dyn Iterator<Item = (SystemTime, Event)>> {
+        if now.duration_since(self.last_pass).unwrap_or(Duration::ZERO) < self.min_delay {
+            // Too soon since the last pass: keep the periodic cadence alive
+            // but skip the re-evaluation itself.
+            return Box::new(
+                [(
+                    now + self.interval,
+                    Event::PlacementPolicy(PlacementMsg::Migrate),
+                )]
+                .into_iter(),
+            );
+        }
+        self.last_pass = now;
+
+        // update idle disks numbers
+        let mut least_idling_disks = Vec::new();
+        for dev in devices.iter() {
+            let idle = self.idle_disks.get_mut(dev.0).unwrap();
+            least_idling_disks.push((dev.0.clone(), dev.1.idle_time().saturating_sub(*idle)));
+            *idle = dev.1.idle_time();
+        }
+        least_idling_disks.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+
+        // Cost estimation identical to `FrequencyPolicy`, but the block's
+        // era (higher = touched more recently) stands in for its access
+        // frequency: a block hot this era outweighs cost the same way a
+        // frequently-read block would.
+        let mut msgs = Vec::new();
+        let mut movements = Vec::new();
+        for (disk_a, disk_idle) in least_idling_disks.iter() {
+            for disk_b in least_idling_disks.iter().rev().filter(|s| s.1 > *disk_idle) {
+                let mut new_blocks_a = Vec::new();
+                let mut new_blocks_b = Vec::new();
+
+                let state_a = devices.get(disk_a).unwrap();
+                let cost_a = state_a
+                    .kind
+                    .sample(&DeviceAccessParams::read(), state_a.current_queue_len());
+                let state_b = devices.get(&disk_b.0).unwrap();
+                let cost_b = state_b
+                    .kind
+                    .sample(&DeviceAccessParams::write(), state_b.current_queue_len());
+
+                for _ in 0..self.reactiveness {
+                    let (a_block, a_recency) = self.blocks.get(disk_a).unwrap().peek_max().unwrap();
+                    let (_, b_recency) = self.blocks.get(&disk_b.0).unwrap().peek_min().unwrap();
+
+                    // Promoting out of a compressing disk_a costs an extra
+                    // decompression penalty; demoting into a compressing
+                    // disk_b writes (and occupies) only the compressed size.
+                    let ratio = self.compressibility.get(a_block).copied().unwrap_or(1.0);
+                    let cost_a_eff = if devices.get(disk_a).unwrap().compressing.is_some() {
+                        cost_a + self.decompression_cost(ratio)
+                    } else {
+                        cost_a
+                    };
+                    let cost_b_eff = if devices.get(&disk_b.0).unwrap().compressing.is_some() {
+                        cost_b.mul_f32(ratio)
+                    } else {
+                        cost_b
+                    };
+                    // Writes to an SSD-class disk_b degrade with how much
+                    // stale, unreclaimed garbage it's already carrying.
+                    let cost_b_eff = cost_b_eff.mul_f32(devices.get(&disk_b.0).unwrap().write_amplification());
+                    let footprint = devices.get(&disk_b.0).unwrap().footprint_estimate(ratio);
+
+                    let state = devices.get(&disk_b.0).unwrap();
+                    if state.free >= footprint
+                        && *a_recency as i128
+                            * (cost_a_eff.as_micros() as i128 - cost_b_eff.as_micros() as i128)
+                            > cost_a_eff.checked_add(cost_b_eff).unwrap().as_micros() as i128
+                    {
+                        let foo = self.blocks.get_mut(disk_a).unwrap();
+                        if foo.is_empty() {
+                            continue;
+                        }
+                        let (block, era) = foo.pop_max().unwrap();
+                        new_blocks_b.push((block, era));
+                        let to_footprint = devices.get_mut(&disk_b.0).unwrap().footprint(block, ratio);
+                        devices.get_mut(&disk_b.0).unwrap().free -= to_footprint;
+                        let from_footprint = devices.get_mut(disk_a).unwrap().release_footprint(&block);
+                        let cur_disk = devices.get_mut(disk_a).unwrap();
+                        cur_disk.free += from_footprint;
+                        msgs.push((
+                            now,
+                            Event::Storage(crate::storage_stack::StorageMsg::Process(
+                                crate::storage_stack::Step::MoveInit(block, disk_b.0.clone()),
+                            )),
+                        ));
+                        if devices.get(disk_a).unwrap().ssd {
+                            msgs.push((
+                                now,
+                                Event::Storage(crate::storage_stack::StorageMsg::Process(
+                                    crate::storage_stack::Step::Discard(block, disk_a.clone()),
+                                )),
+                            ));
+                        }
+                    } else {
+                        if self.blocks.get(disk_a).unwrap().is_empty() {
+                            break;
+                        }
+
+                        if *a_recency as i128
+                            * (cost_a_eff.as_micros() as i128 - cost_b_eff.as_micros() as i128)
+                            - *b_recency as i128
+                                * (cost_b_eff.as_micros() as i128 - cost_a_eff.as_micros() as i128)
+                            > 2 * cost_a_eff.checked_add(cost_b_eff).unwrap().as_micros() as i128
+                        {
+                            let (a_block, a_era) =
+                                self.blocks.get_mut(disk_a).unwrap().pop_max().unwrap();
+                            let queue_b = self.blocks.get_mut(&disk_b.0).unwrap();
+                            let (b_block, b_era) = queue_b.pop_min().unwrap();
+                            new_blocks_a.push((b_block, b_era));
+                            new_blocks_b.push((a_block, a_era));
+                            msgs.push((
+                                now,
+                                Event::Storage(crate::storage_stack::StorageMsg::Process(
+                                    crate::storage_stack::Step::MoveInit(a_block, disk_b.0.clone()),
+                                )),
+                            ));
+                            msgs.push((
+                                now,
+                                Event::Storage(crate::storage_stack::StorageMsg::Process(
+                                    crate::storage_stack::Step::MoveInit(b_block, disk_a.clone()),
+                                )),
+                            ));
+                            if devices.get(disk_a).unwrap().ssd {
+                                msgs.push((
+                                    now,
+                                    Event::Storage(crate::storage_stack::StorageMsg::Process(
+                                        crate::storage_stack::Step::Discard(a_block, disk_a.clone()),
+                                    )),
+                                ));
+                            }
+                            if devices.get(&disk_b.0).unwrap().ssd {
+                                msgs.push((
+                                    now,
+                                    Event::Storage(crate::storage_stack::StorageMsg::Process(
+                                        crate::storage_stack::Step::Discard(b_block, disk_b.0.clone()),
+                                    )),
+                                ));
+                            }
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                let queue_a = self.blocks.get_mut(disk_a).unwrap();
+                for b in new_blocks_a.iter() {
+                    queue_a.push(b.0.clone(), b.1);
+                }
+                movements.push(MovementInfo {
+                    from: disk_b.0.clone(),
+                    to: disk_a.clone(),
+                    size: new_blocks_a.len(),
+                });
+                let queue_b = self.blocks.get_mut(&disk_b.0).unwrap();
+                for b in new_blocks_b.iter() {
+                    queue_b.push(b.0.clone(), b.1);
+                }
+                movements.push(MovementInfo {
+                    from: disk_a.clone(),
+                    to: disk_b.0.clone(),
+                    size: new_blocks_b.len(),
+                });
+            }
+        }
+
+        // Era bookkeeping: a block untouched for more than
+        // `cold_eras_threshold` eras is cold, so age it down to the lowest
+        // priority directly. This scans every block's `last_touched_era`;
+        // the writesets are not consulted.
+        for (block, era) in self.last_touched_era.iter() {
+            if self.current_era.saturating_sub(*era) > self.cold_eras_threshold {
+                if let Some(dev) = _blocks.get(block) {
+                    if let Some(queue) = self.blocks.get_mut(dev) {
+                        queue.change_priority(block, 0);
+                    }
+                }
+            }
+        }
+
+        self.current_era += 1;
+        self.writesets
+            .push_back(FixedBitSet::with_capacity(self.block_ordinal.len()));
+        while self.writesets.len() > self.cold_eras_threshold as usize + 1 {
+            self.writesets.pop_front();
+        }
+
+        tx.send(ResMsg::Policy {
+            now,
+            decided_at: now,
+            moved: movements,
+        })
+        .unwrap();
+        Box::new(msgs.into_iter().chain([(
+            now + self.interval,
+            Event::PlacementPolicy(PlacementMsg::Migrate),
+        )]))
+    }
+}
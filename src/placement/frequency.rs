@@ -1,5 +1,8 @@
 use std::{
     collections::HashMap,
+    fs::File,
+    io::BufWriter,
+    path::PathBuf,
     time::{Duration, SystemTime},
 };
 
@@ -12,7 +15,7 @@ use crate::{
     Block, Event,
 };
 
-use super::{PlacementMsg, PlacementPolicy};
+use super::{JournalWriter, MigrationRecord, PlacementMsg, PlacementPolicy};
 
 /// Simple Example policy.
 /// Keeping track of blocks and promoting them eventually.
@@ -23,23 +26,178 @@ pub struct FrequencyPolicy {
     reactiveness: usize,
     decay: f32,
     interval: Duration,
+    /// Migrations currently in flight toward a given destination disk, as
+    /// `(block, frequency it was moved with, source disk, completes_at)`. A
+    /// block stays out of its destination's [`DoublePriorityQueue`] and
+    /// `free` isn't moved from the source to the destination until its
+    /// [`super::PlacementMsg::MigrateComplete`] fires.
+    /// Fifth element is when the migration was decided, so a later
+    /// `complete_migration` can report decision-vs-execution lag instead of
+    /// only the completion timestamp.
+    in_flight: HashMap<DiskId, Vec<(Block, u64, DiskId, SystemTime, SystemTime)>>,
+    /// Queue depth: maximum number of migrations allowed in flight toward
+    /// any single destination disk at once.
+    max_in_flight: usize,
+    /// Shared migration bandwidth budget, in bytes/s, that a disk's
+    /// concurrent incoming transfers divide between themselves.
+    migration_bandwidth: f64,
+    /// Write-ahead log of completed migrations, so a run can be resumed or
+    /// bit-identically rereplayed. Absent unless a journal path was given.
+    journal: Option<JournalWriter<BufWriter<File>>>,
+    /// Per-block compressibility ratio (fraction of its size retained after
+    /// compression), sampled once at `init` from `[compress_min, compress_max]`.
+    /// Only matters for blocks that end up on a compressing tier.
+    compressibility: HashMap<Block, f32>,
+    compress_min: f32,
+    compress_max: f32,
+    /// Decompression throughput, in bytes/s, charged as an extra read cost
+    /// when promoting a block off a compressing tier.
+    decompression_bandwidth: f64,
+    /// Floor on how often `migrate()` actually does work: a call before
+    /// `last_pass + min_delay` has elapsed re-arms the next attempt but
+    /// skips the re-evaluation itself.
+    min_delay: Duration,
+    last_pass: SystemTime,
 
     _low_threshold: f32,
     _high_threshold: f32,
 }
 
 impl FrequencyPolicy {
-    pub fn new(interval: Duration, reactiveness: usize, decay: f32) -> Self {
+    pub fn new(
+        interval: Duration,
+        reactiveness: usize,
+        decay: f32,
+        max_in_flight: usize,
+        migration_bandwidth: f64,
+        journal_path: Option<PathBuf>,
+        compress_min: f32,
+        compress_max: f32,
+        decompression_bandwidth: f64,
+        min_delay: Duration,
+    ) -> Self {
+        let journal = journal_path.map(|path| {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .expect("journal path must be writable");
+            JournalWriter::new(BufWriter::new(file))
+        });
         FrequencyPolicy {
             blocks: HashMap::new(),
             idle_disks: HashMap::new(),
             reactiveness,
             interval,
             decay,
+            in_flight: HashMap::new(),
+            max_in_flight,
+            migration_bandwidth,
+            journal,
+            compressibility: HashMap::new(),
+            compress_min,
+            compress_max,
+            decompression_bandwidth,
+            min_delay,
+            last_pass: SystemTime::UNIX_EPOCH,
             _low_threshold: 0.,
             _high_threshold: 0.,
         }
     }
+
+    /// Extra read cost from decompressing a block with the given
+    /// compressibility `ratio` back out, proportional to its compressed size.
+    fn decompression_cost(&self, ratio: f32) -> Duration {
+        Duration::from_secs_f64(BLOCK_SIZE_IN_B as f64 * ratio as f64 / self.decompression_bandwidth)
+    }
+
+    /// Completion time for one block: the larger of source-read +
+    /// destination-write cost and its transfer time at `migration_bandwidth`,
+    /// multiplied by how many transfers are sharing `disk_b`'s migration
+    /// bandwidth right now (including the one about to be scheduled).
+    fn completion_time(
+        &self,
+        now: SystemTime,
+        cost_a: Duration,
+        cost_b: Duration,
+        concurrency: usize,
+    ) -> SystemTime {
+        let io_cost = cost_a + cost_b;
+        let bandwidth_cost =
+            Duration::from_secs_f64(BLOCK_SIZE_IN_B as f64 / self.migration_bandwidth);
+        now + io_cost.max(bandwidth_cost) * concurrency.max(1) as u32
+    }
+
+    /// Apply the bookkeeping for a migration that was issued in some earlier
+    /// `migrate` call and has now actually finished: the block rejoins
+    /// `to_disk`'s priority queue, space is moved from the source to the
+    /// destination, and the completed transfer is reported as a
+    /// throughput-over-time data point.
+    fn complete_migration(
+        &mut self,
+        block: Block,
+        to_disk: DiskId,
+        devices: &mut HashMap<DiskId, DeviceState>,
+        now: SystemTime,
+        tx: &mut Sender<ResMsg>,
+    ) -> Box<dyn Iterator<Item = (SystemTime, Event)>> {
+        let in_flight = self.in_flight.get_mut(&to_disk).unwrap();
+        let pos = in_flight
+            .iter()
+            .position(|(b, ..)| *b == block)
+            .expect("a completing migration must have been tracked as in flight");
+        let (block, freq, from_disk, _, decided_at) = in_flight.remove(pos);
+
+        let ratio = self.compressibility.get(&block).copied().unwrap_or(1.0);
+        let to_footprint = devices.get_mut(&to_disk).unwrap().footprint(block, ratio);
+        let from_footprint = devices.get_mut(&from_disk).unwrap().release_footprint(&block);
+        devices.get_mut(&to_disk).unwrap().free -= to_footprint;
+        devices.get_mut(&from_disk).unwrap().free += from_footprint;
+        self.blocks.get_mut(&to_disk).unwrap().push(block, freq);
+
+        if let Some(journal) = self.journal.as_mut() {
+            journal
+                .append(&MigrationRecord {
+                    now,
+                    from: from_disk.clone(),
+                    to: to_disk.clone(),
+                    blocks: vec![block],
+                })
+                .expect("journal must remain writable for the life of the run");
+            journal.flush().expect("journal must remain writable");
+        }
+
+        tx.send(ResMsg::Policy {
+            now,
+            decided_at,
+            moved: vec![MovementInfo {
+                from: from_disk.clone(),
+                to: to_disk.clone(),
+                size: 1,
+            }],
+        })
+        .unwrap();
+
+        Box::new([].into_iter())
+    }
+
+    /// Fast-forward `self.blocks` from a journaled run: each journaled move
+    /// is re-applied against the priority queues directly, at priority 0
+    /// (the journal doesn't carry per-block frequency, only which block went
+    /// where), so a resumed run at least starts from the right placement
+    /// even if access-frequency ranking has to rebuild from fresh traffic.
+    fn replay_journal(&mut self, journal: &[MigrationRecord]) {
+        for record in journal {
+            for block in &record.blocks {
+                if let Some(queue) = self.blocks.get_mut(&record.from) {
+                    queue.remove(block);
+                }
+                if let Some(queue) = self.blocks.get_mut(&record.to) {
+                    queue.push(*block, 0);
+                }
+            }
+        }
+    }
 }
 
 impl PlacementPolicy for FrequencyPolicy {
@@ -47,18 +205,25 @@ impl PlacementPolicy for FrequencyPolicy {
         &mut self,
         devices: &HashMap<DiskId, DeviceState>,
         blocks: &HashMap<Block, DiskId>,
+        _subs: &mut crate::storage_stack::SubscriptionManager,
         now: SystemTime,
     ) -> Box<dyn Iterator<Item = (std::time::SystemTime, crate::Event)>> {
         for dev in devices {
             self.blocks
                 .insert(dev.0.clone(), DoublePriorityQueue::new());
             self.idle_disks.insert(dev.0.clone(), Duration::ZERO);
+            self.in_flight.insert(dev.0.clone(), Vec::new());
         }
+        let mut rng = rand::thread_rng();
         for block in blocks {
             self.blocks
                 .get_mut(block.1)
                 .unwrap()
                 .push(block.0.clone(), 0);
+            self.compressibility.insert(
+                block.0.clone(),
+                rand::Rng::gen_range(&mut rng, self.compress_min..=self.compress_max),
+            );
         }
         Box::new(
             [(
@@ -79,6 +244,9 @@ impl PlacementPolicy for FrequencyPolicy {
     ) -> Box<dyn Iterator<Item = (std::time::SystemTime, crate::Event)>> {
         match msg {
             PlacementMsg::Migrate => return self.migrate(devices, blocks, now, tx),
+            PlacementMsg::MigrateComplete(block, to_disk) => {
+                return self.complete_migration(block, to_disk, devices, now, tx)
+            }
             _ => {}
         }
         let block = msg.block();
@@ -106,12 +274,25 @@ impl PlacementPolicy for FrequencyPolicy {
         now: SystemTime,
         tx: &mut Sender<ResMsg>,
     ) -> Box<dyn Iterator<Item = (std::time::SystemTime, crate::Event)>> {
+        if now.duration_since(self.last_pass).unwrap_or(Duration::ZERO) < self.min_delay {
+            // Too soon since the last pass: keep the periodic cadence alive
+            // but skip the re-evaluation itself.
+            return Box::new(
+                [(
+                    now + self.interval,
+                    Event::PlacementPolicy(PlacementMsg::Migrate),
+                )]
+                .into_iter(),
+            );
+        }
+        self.last_pass = now;
+
         // update idle disks numbers
         let mut least_idling_disks = Vec::new();
         for dev in devices.iter() {
             let idle = self.idle_disks.get_mut(dev.0).unwrap();
-            least_idling_disks.push((dev.0.clone(), dev.1.idle_time.saturating_sub(*idle)));
-            *idle = dev.1.idle_time;
+            least_idling_disks.push((dev.0.clone(), dev.1.idle_time().saturating_sub(*idle)));
+            *idle = dev.1.idle_time();
         }
         least_idling_disks.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
 
@@ -134,12 +315,8 @@ impl PlacementPolicy for FrequencyPolicy {
         //
         // Take note, that costs are simplified and might diff between read/write.
         let mut msgs = Vec::new();
-        let mut movements = Vec::new();
         for (disk_a, disk_idle) in least_idling_disks.iter() {
             for disk_b in least_idling_disks.iter().rev().filter(|s| s.1 > *disk_idle) {
-                let mut new_blocks_a = Vec::new();
-                let mut new_blocks_b = Vec::new();
-
                 // FIXME: These operations should be replaced with hypotheticals for actual runs.
                 let state_a = devices.get_mut(disk_a).unwrap();
                 let cost_a = state_a
@@ -151,51 +328,113 @@ impl PlacementPolicy for FrequencyPolicy {
                     .write(BLOCK_SIZE_IN_B as u64, crate::storage_stack::Ap::Random);
 
                 for _ in 0..self.reactiveness {
-                    let (_, a_block_freq) = self.blocks.get(disk_a).unwrap().peek_max().unwrap();
+                    let (a_block, a_block_freq) =
+                        self.blocks.get(disk_a).unwrap().peek_max().unwrap();
                     let (_, b_block_freq) = self.blocks.get(&disk_b.0).unwrap().peek_min().unwrap();
 
+                    // A promotes out of disk_a: pay a decompression penalty on
+                    // top of the raw read if disk_a compresses. A demotes
+                    // into disk_b: the write itself shrinks to the
+                    // compressed size if disk_b compresses.
+                    let ratio = self.compressibility.get(a_block).copied().unwrap_or(1.0);
+                    let cost_a_eff = if devices.get(disk_a).unwrap().compressing.is_some() {
+                        cost_a + self.decompression_cost(ratio)
+                    } else {
+                        cost_a
+                    };
+                    let cost_b_eff = if devices.get(&disk_b.0).unwrap().compressing.is_some() {
+                        cost_b.mul_f32(ratio)
+                    } else {
+                        cost_b
+                    };
+                    // Writes to an SSD-class disk_b degrade with how much
+                    // stale, unreclaimed garbage it's already carrying.
+                    let cost_b_eff = cost_b_eff.mul_f32(devices.get(&disk_b.0).unwrap().write_amplification());
+
+                    // Projected free space on disk_b accounts for transfers
+                    // already in flight toward it, since their `free`
+                    // decrement is deferred until they complete.
+                    let footprint = devices.get(&disk_b.0).unwrap().footprint_estimate(ratio);
+                    let in_flight_b = self.in_flight.get(&disk_b.0).unwrap().len();
                     let state = devices.get_mut(&disk_b.0).unwrap();
-                    if state.free > 0
+                    let projected_free = state.free.saturating_sub(in_flight_b * footprint.max(1));
+                    if projected_free >= footprint
+                        && in_flight_b < self.max_in_flight
                         && *a_block_freq as i128
-                            * (cost_a.as_micros() as i128 - cost_b.as_micros() as i128)
-                            > cost_a.checked_add(cost_b).unwrap().as_micros() as i128
+                            * (cost_a_eff.as_micros() as i128 - cost_b_eff.as_micros() as i128)
+                            > cost_a_eff.checked_add(cost_b_eff).unwrap().as_micros() as i128
                     {
-                        // Space is available for migration and should be used
-                        // Migration handled internally on storage stack
-                        // Data is blocked until completion
+                        // Queue depth allows another transfer toward disk_b;
+                        // issue the real move and track it as in flight.
                         let foo = self.blocks.get_mut(disk_a).unwrap();
                         if foo.is_empty() {
                             continue;
                         }
                         let (block, freq) = foo.pop_max().unwrap();
-                        new_blocks_b.push((block, freq));
-                        // self.blocks.get_mut(&disk_b.0).unwrap().push(block, freq);
-                        state.free -= 1;
-                        let cur_disk = devices.get_mut(disk_a).unwrap();
-                        cur_disk.free += 1;
+                        let completes_at =
+                            self.completion_time(now, cost_a_eff, cost_b_eff, in_flight_b + 1);
+                        self.in_flight
+                            .get_mut(&disk_b.0)
+                            .unwrap()
+                            .push((block, freq, disk_a.clone(), completes_at, now));
                         msgs.push((
                             now,
                             Event::Storage(crate::storage_stack::StorageMsg::Process(
                                 crate::storage_stack::Step::MoveInit(block, disk_b.0.clone()),
                             )),
                         ));
+                        if devices.get(disk_a).unwrap().ssd {
+                            msgs.push((
+                                now,
+                                Event::Storage(crate::storage_stack::StorageMsg::Process(
+                                    crate::storage_stack::Step::Discard(block, disk_a.clone()),
+                                )),
+                            ));
+                        }
+                        msgs.push((
+                            completes_at,
+                            Event::PlacementPolicy(PlacementMsg::MigrateComplete(
+                                block,
+                                disk_b.0.clone(),
+                            )),
+                        ));
                     } else {
                         if self.blocks.get(disk_a).unwrap().is_empty() {
                             break;
                         }
 
-                        if *a_block_freq as i128
-                            * (cost_a.as_micros() as i128 - cost_b.as_micros() as i128)
-                            - *b_block_freq as i128
-                                * (cost_b.as_micros() as i128 - cost_a.as_micros() as i128)
-                            > 2 * cost_a.checked_add(cost_b).unwrap().as_micros() as i128
+                        let in_flight_a = self.in_flight.get(disk_a).unwrap().len();
+                        if in_flight_a < self.max_in_flight
+                            && in_flight_b < self.max_in_flight
+                            && *a_block_freq as i128
+                                * (cost_a_eff.as_micros() as i128 - cost_b_eff.as_micros() as i128)
+                                - *b_block_freq as i128
+                                    * (cost_b_eff.as_micros() as i128 - cost_a_eff.as_micros() as i128)
+                                > 2 * cost_a_eff.checked_add(cost_b_eff).unwrap().as_micros() as i128
                         {
                             let (a_block, a_block_freq) =
                                 self.blocks.get_mut(disk_a).unwrap().pop_max().unwrap();
                             let queue_b = self.blocks.get_mut(&disk_b.0).unwrap();
                             let (b_block, b_block_freq) = queue_b.pop_min().unwrap();
-                            new_blocks_a.push((b_block, b_block_freq));
-                            new_blocks_b.push((a_block, a_block_freq));
+
+                            let a_completes_at =
+                                self.completion_time(now, cost_a_eff, cost_b_eff, in_flight_b + 1);
+                            let b_completes_at =
+                                self.completion_time(now, cost_b_eff, cost_a_eff, in_flight_a + 1);
+                            self.in_flight.get_mut(&disk_b.0).unwrap().push((
+                                a_block,
+                                a_block_freq,
+                                disk_a.clone(),
+                                a_completes_at,
+                                now,
+                            ));
+                            self.in_flight.get_mut(disk_a).unwrap().push((
+                                b_block,
+                                b_block_freq,
+                                disk_b.0.clone(),
+                                b_completes_at,
+                                now,
+                            ));
                             msgs.push((
                                 now,
                                 Event::Storage(crate::storage_stack::StorageMsg::Process(
@@ -208,29 +447,41 @@ impl PlacementPolicy for FrequencyPolicy {
                                     crate::storage_stack::Step::MoveInit(b_block, disk_a.clone()),
                                 )),
                             ));
+                            if devices.get(disk_a).unwrap().ssd {
+                                msgs.push((
+                                    now,
+                                    Event::Storage(crate::storage_stack::StorageMsg::Process(
+                                        crate::storage_stack::Step::Discard(a_block, disk_a.clone()),
+                                    )),
+                                ));
+                            }
+                            if devices.get(&disk_b.0).unwrap().ssd {
+                                msgs.push((
+                                    now,
+                                    Event::Storage(crate::storage_stack::StorageMsg::Process(
+                                        crate::storage_stack::Step::Discard(b_block, disk_b.0.clone()),
+                                    )),
+                                ));
+                            }
+                            msgs.push((
+                                a_completes_at,
+                                Event::PlacementPolicy(PlacementMsg::MigrateComplete(
+                                    a_block,
+                                    disk_b.0.clone(),
+                                )),
+                            ));
+                            msgs.push((
+                                b_completes_at,
+                                Event::PlacementPolicy(PlacementMsg::MigrateComplete(
+                                    b_block,
+                                    disk_a.clone(),
+                                )),
+                            ));
                         } else {
                             break;
                         }
                     }
                 }
-                let queue_a = self.blocks.get_mut(disk_a).unwrap();
-                for b in new_blocks_a.iter() {
-                    queue_a.push(b.0, b.1);
-                }
-                movements.push(MovementInfo {
-                    from: disk_b.0.clone(),
-                    to: disk_a.clone(),
-                    size: new_blocks_a.len(),
-                });
-                let queue_b = self.blocks.get_mut(&disk_b.0).unwrap();
-                for b in new_blocks_b.iter() {
-                    queue_b.push(b.0, b.1);
-                }
-                movements.push(MovementInfo {
-                    from: disk_a.clone(),
-                    to: disk_b.0.clone(),
-                    size: new_blocks_b.len(),
-                });
             }
         }
 
@@ -240,14 +491,22 @@ impl PlacementPolicy for FrequencyPolicy {
             }
         }
 
-        tx.send(ResMsg::Policy {
-            now,
-            moved: movements,
-        })
-        .unwrap();
         Box::new(msgs.into_iter().chain([(
             now + self.interval,
             Event::PlacementPolicy(PlacementMsg::Migrate),
         )]))
     }
+
+    fn replay(&mut self, journal: &[MigrationRecord]) {
+        for record in journal {
+            for block in &record.blocks {
+                if let Some(queue) = self.blocks.get_mut(&record.from) {
+                    queue.remove(block);
+                }
+                if let Some(queue) = self.blocks.get_mut(&record.to) {
+                    queue.push(*block, 0);
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,156 @@
+use std::{
+    io::{Read, Write},
+    time::SystemTime,
+};
+
+use crc32fast::Hasher;
+use serde::{Deserialize, Serialize};
+
+use crate::{storage_stack::DiskId, Block};
+
+const HEADER_SIZE: usize = 9;
+
+/// Size of one ring-log block. A record whose encoding doesn't fit in a
+/// single block's payload capacity is split across consecutive
+/// `First`/`Middle`/`Last` blocks and reassembled on read -- the same
+/// framing a write-ahead log uses to survive a crash mid-record.
+pub const RING_BLOCK_SIZE: usize = 512;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+enum RecordType {
+    Full = 0,
+    First = 1,
+    Middle = 2,
+    Last = 3,
+}
+
+impl RecordType {
+    fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(RecordType::Full),
+            1 => Some(RecordType::First),
+            2 => Some(RecordType::Middle),
+            3 => Some(RecordType::Last),
+            _ => None,
+        }
+    }
+}
+
+/// One completed migration decision, as replayed from the journal: which
+/// blocks moved, from where, to where, and when.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MigrationRecord {
+    pub now: SystemTime,
+    pub from: DiskId,
+    pub to: DiskId,
+    pub blocks: Vec<Block>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum JournalError {
+    #[error("Could not encode migration record: {0}")]
+    Encode(#[from] bincode::Error),
+    #[error("Could not write journal record: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Appends [`MigrationRecord`]s to a write-ahead ring log, framing each one
+/// with a CRC32-checked header so a reader can detect a torn write left by an
+/// interrupted run.
+pub struct JournalWriter<W> {
+    out: W,
+}
+
+impl<W: Write> JournalWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    pub fn append(&mut self, record: &MigrationRecord) -> Result<(), JournalError> {
+        let payload = bincode::serialize(record)?;
+        let capacity = RING_BLOCK_SIZE - HEADER_SIZE;
+        let chunks: Vec<&[u8]> = if payload.is_empty() {
+            vec![&payload[..]]
+        } else {
+            payload.chunks(capacity).collect()
+        };
+        let last = chunks.len() - 1;
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            let rtype = match i {
+                _ if last == 0 => RecordType::Full,
+                0 => RecordType::First,
+                i if i == last => RecordType::Last,
+                _ => RecordType::Middle,
+            };
+            let mut hasher = Hasher::new();
+            hasher.update(chunk);
+            self.out.write_all(&hasher.finalize().to_le_bytes())?;
+            self.out.write_all(&(chunk.len() as u32).to_le_bytes())?;
+            self.out.write_all(&[rtype as u8])?;
+            self.out.write_all(chunk)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), JournalError> {
+        Ok(self.out.flush()?)
+    }
+}
+
+/// Replays [`MigrationRecord`]s from a write-ahead ring log written by
+/// [`JournalWriter`].
+pub struct JournalReader<R> {
+    input: R,
+}
+
+impl<R: Read> JournalReader<R> {
+    pub fn new(input: R) -> Self {
+        Self { input }
+    }
+
+    /// Reads every intact record in order. Stops at the first record whose
+    /// CRC doesn't check out, and treats a short read while fetching a
+    /// trailing header or payload as truncation from an interrupted run
+    /// rather than corruption -- both simply end replay with whatever was
+    /// read so far.
+    pub fn replay(mut self) -> Vec<MigrationRecord> {
+        let mut records = Vec::new();
+        let mut pending = Vec::new();
+        loop {
+            let mut header = [0u8; HEADER_SIZE];
+            if self.input.read_exact(&mut header).is_err() {
+                break;
+            }
+            let crc32 = u32::from_le_bytes(header[0..4].try_into().unwrap());
+            let rsize = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+            let Some(rtype) = RecordType::from_u8(header[8]) else {
+                break;
+            };
+
+            let mut payload = vec![0u8; rsize];
+            if self.input.read_exact(&mut payload).is_err() {
+                break;
+            }
+
+            let mut hasher = Hasher::new();
+            hasher.update(&payload);
+            if hasher.finalize() != crc32 {
+                break;
+            }
+            pending.extend_from_slice(&payload);
+
+            match rtype {
+                RecordType::Full | RecordType::Last => {
+                    match bincode::deserialize(&pending) {
+                        Ok(record) => records.push(record),
+                        Err(_) => break,
+                    }
+                    pending.clear();
+                }
+                RecordType::First | RecordType::Middle => {}
+            }
+        }
+        records
+    }
+}
@@ -1,17 +1,32 @@
 use std::{
     collections::HashMap,
+    path::PathBuf,
     time::{Duration, SystemTime},
 };
 
-use crate::{result_csv::ResMsg, storage_stack::DeviceState, Block, Event};
+use crate::{
+    result_csv::ResMsg,
+    storage_stack::{DeviceEventKind, DeviceState, DiskId, SubscriptionManager},
+    Block, Event,
+};
 
+mod arc;
+mod era;
 mod frequency;
+mod journal;
 mod noop;
+mod quarantine;
+mod recency;
 
 use crossbeam::channel::Sender;
 use duration_str::deserialize_duration;
+pub use arc::ArcPolicy;
+pub use era::EraPolicy;
 pub use frequency::FrequencyPolicy;
+pub use journal::{JournalError, JournalReader, JournalWriter, MigrationRecord, RING_BLOCK_SIZE};
 pub use noop::Noop;
+pub use quarantine::QuarantinePolicy;
+pub use recency::RecencyPolicy;
 use serde::Deserialize;
 
 #[derive(Deserialize)]
@@ -21,18 +36,152 @@ pub enum PlacementConfig {
         interval: Duration,
         reactiveness: usize,
         decay: f32,
+        /// Maximum number of migrations allowed in flight toward any single
+        /// destination disk at once, modeling a bounded queue depth.
+        max_in_flight: usize,
+        /// Shared migration bandwidth budget, in bytes/s, a disk's
+        /// concurrent incoming transfers divide between themselves.
+        migration_bandwidth: f64,
+        /// Write-ahead log of completed migrations, for crash-consistent
+        /// resume/replay. No journal is kept if absent.
+        journal_path: Option<PathBuf>,
+        /// Range a block's compressibility ratio (fraction of its size
+        /// retained after compression) is sampled from at init.
+        compress_min: f32,
+        compress_max: f32,
+        /// Decompression throughput, in bytes/s, charged as an extra read
+        /// cost when promoting a block off a compressing tier.
+        decompression_bandwidth: f64,
+        /// Floor on how often `migrate()` actually re-evaluates placement;
+        /// a call before `min_delay` has elapsed re-arms the next periodic
+        /// attempt but skips the work itself.
+        #[serde(deserialize_with = "deserialize_duration")]
+        min_delay: Duration,
+    },
+    Era {
+        #[serde(deserialize_with = "deserialize_duration")]
+        interval: Duration,
+        reactiveness: usize,
+        cold_eras_threshold: u32,
+        /// Range a block's compressibility ratio (fraction of its size
+        /// retained after compression) is sampled from at init.
+        compress_min: f32,
+        compress_max: f32,
+        /// Decompression throughput, in bytes/s, charged as an extra read
+        /// cost when promoting a block off a compressing tier.
+        decompression_bandwidth: f64,
+        /// Floor on how often `migrate()` actually re-evaluates placement;
+        /// a call before `min_delay` has elapsed re-arms the next periodic
+        /// attempt but skips the work itself.
+        #[serde(deserialize_with = "deserialize_duration")]
+        min_delay: Duration,
+    },
+    Recency {
+        /// Blocks untouched for longer than this are demotion candidates.
+        #[serde(deserialize_with = "deserialize_duration")]
+        cooldown: Duration,
+        /// The `migrate()` re-scan period; also the recency window a block
+        /// must fall within to be a promotion candidate.
+        #[serde(deserialize_with = "deserialize_duration")]
+        interval: Duration,
+        /// Caps how many blocks are promoted in a single `migrate()` pass.
+        promote_batch: usize,
+        /// Floor on how often `migrate()` actually re-evaluates placement;
+        /// a call before `min_delay` has elapsed re-arms the next periodic
+        /// attempt but skips the work itself.
+        #[serde(deserialize_with = "deserialize_duration")]
+        min_delay: Duration,
+    },
+    Quarantine {
+        /// `DeviceState::health` at or above this marks a device for
+        /// quarantine.
+        threshold: f64,
+        /// Once health has dropped back below `threshold`, quarantine is
+        /// only lifted after this much time has passed since it began.
+        #[serde(deserialize_with = "deserialize_duration")]
+        recovery: Duration,
+        /// The `migrate()` re-scan period.
+        #[serde(deserialize_with = "deserialize_duration")]
+        interval: Duration,
+    },
+    /// Adaptive Replacement Cache, applied to which device a block lives on
+    /// instead of in-process caching.
+    Arc {
+        /// Total fast-tier capacity, in blocks.
+        capacity: usize,
     },
     Noop,
 }
 
 impl PlacementConfig {
+    /// The write-ahead journal path this config was given, if any, so a
+    /// caller resuming a run can feed [`JournalReader::replay`]'s output into
+    /// [`PlacementPolicy::replay`] without knowing which variant it built.
+    pub fn journal_path(&self) -> Option<&PathBuf> {
+        match self {
+            PlacementConfig::Frequency { journal_path, .. } => journal_path.as_ref(),
+            PlacementConfig::Era { .. }
+            | PlacementConfig::Recency { .. }
+            | PlacementConfig::Quarantine { .. }
+            | PlacementConfig::Arc { .. }
+            | PlacementConfig::Noop => None,
+        }
+    }
+
     pub fn build(&self) -> Box<dyn PlacementPolicy> {
         match self {
             PlacementConfig::Frequency {
                 interval,
                 reactiveness,
                 decay,
-            } => Box::new(FrequencyPolicy::new(*interval, *reactiveness, *decay)),
+                max_in_flight,
+                migration_bandwidth,
+                journal_path,
+                compress_min,
+                compress_max,
+                decompression_bandwidth,
+                min_delay,
+            } => Box::new(FrequencyPolicy::new(
+                *interval,
+                *reactiveness,
+                *decay,
+                *max_in_flight,
+                *migration_bandwidth,
+                journal_path.clone(),
+                *compress_min,
+                *compress_max,
+                *decompression_bandwidth,
+                *min_delay,
+            )),
+            PlacementConfig::Era {
+                interval,
+                reactiveness,
+                cold_eras_threshold,
+                compress_min,
+                compress_max,
+                decompression_bandwidth,
+                min_delay,
+            } => Box::new(EraPolicy::new(
+                *interval,
+                *reactiveness,
+                *cold_eras_threshold,
+                *compress_min,
+                *compress_max,
+                *decompression_bandwidth,
+                *min_delay,
+            )),
+            PlacementConfig::Recency {
+                cooldown,
+                interval,
+                promote_batch,
+                min_delay,
+            } => Box::new(RecencyPolicy::new(*cooldown, *interval, *promote_batch, *min_delay)),
+            PlacementConfig::Quarantine {
+                threshold,
+                recovery,
+                interval,
+            } => Box::new(QuarantinePolicy::new(*threshold, *recovery, *interval)),
+            PlacementConfig::Arc { capacity } => Box::new(ArcPolicy::new(*capacity)),
             PlacementConfig::Noop => Box::new(Noop {}),
         }
     }
@@ -42,13 +191,29 @@ impl PlacementConfig {
 pub enum PlacementMsg {
     Fetched(Block),
     Written(Block),
+    Discarded(Block),
     Migrate,
+    /// A previously-issued migration of `Block` into the given disk has
+    /// finished. Deferred bookkeeping (destination free-space accounting
+    /// and priority-queue reinsertion) happens here rather than at issue
+    /// time, since the transfer is still in flight until now.
+    MigrateComplete(Block, DiskId),
+    /// One of this policy's own `init()`-time `SubscriptionManager`
+    /// registrations just crossed its threshold, sparing it an O(devices)
+    /// re-scan on every `update()` call.
+    DeviceEvent {
+        device: DiskId,
+        kind: DeviceEventKind,
+    },
 }
 
 impl PlacementMsg {
     pub fn block(&self) -> &Block {
         match self {
-            PlacementMsg::Fetched(block) | PlacementMsg::Written(block) => block,
+            PlacementMsg::Fetched(block)
+            | PlacementMsg::Written(block)
+            | PlacementMsg::Discarded(block) => block,
+            PlacementMsg::MigrateComplete(block, _) => block,
             _ => unimplemented!(),
         }
     }
@@ -56,10 +221,14 @@ impl PlacementMsg {
 
 /// A policy adjusting data placement live.
 pub trait PlacementPolicy {
+    /// `subs` lets this policy register capacity thresholds it wants to hear
+    /// about later as `PlacementMsg::DeviceEvent`, instead of re-deriving
+    /// them from `devices` on every `update()` call.
     fn init(
         &mut self,
         devices: &HashMap<String, DeviceState>,
         blocks: &HashMap<Block, String>,
+        subs: &mut SubscriptionManager,
         now: SystemTime,
     ) -> Box<dyn Iterator<Item = (SystemTime, Event)>>;
     fn update(
@@ -77,4 +246,13 @@ pub trait PlacementPolicy {
         now: SystemTime,
         tx: &mut Sender<ResMsg>,
     ) -> Box<dyn Iterator<Item = (SystemTime, Event)>>;
+    /// Fast-forward this policy's internal bookkeeping from a previously
+    /// recorded [`MigrationRecord`] log, so a run can resume or be
+    /// bit-identically rereplayed instead of recomputing `migrate` from
+    /// scratch. Device/block placement itself is restored separately via
+    /// [`crate::storage_stack::StorageStack::snapshot`]; this only needs to
+    /// resynchronize whatever the policy tracks on its own (e.g. per-block
+    /// priority queues). Policies that don't keep such state can leave the
+    /// default no-op.
+    fn replay(&mut self, _journal: &[MigrationRecord]) {}
 }
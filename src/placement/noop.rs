@@ -4,7 +4,7 @@ use crossbeam::channel::Sender;
 
 use crate::{
     result_csv::ResMsg,
-    storage_stack::{DeviceState, DiskId},
+    storage_stack::{DeviceState, DiskId, SubscriptionManager},
     Block,
 };
 
@@ -17,6 +17,7 @@ impl PlacementPolicy for Noop {
         &mut self,
         _devices: &HashMap<DiskId, DeviceState>,
         _blocks: &HashMap<Block, DiskId>,
+        _subs: &mut SubscriptionManager,
         _now: SystemTime,
     ) -> Box<dyn Iterator<Item = (std::time::SystemTime, crate::Event)>> {
         Box::new([].into_iter())
@@ -0,0 +1,174 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+
+use crossbeam::channel::Sender;
+
+use crate::{
+    result_csv::{MovementInfo, ResMsg},
+    storage_stack::{DeviceState, DiskId, SubscriptionManager},
+    Block, Event,
+};
+
+use super::{PlacementMsg, PlacementPolicy};
+
+/// Evacuates devices whose `DeviceState::health` has crossed `threshold`:
+/// on every `migrate()` pass it first updates quarantine membership, then
+/// proactively drains any still-quarantined device's blocks to the
+/// healthiest non-quarantined device with free capacity. Note that new
+/// block placement (`StorageStack::insert`) isn't policy-driven anywhere in
+/// this simulator, so "stops directing new writes there" only actually
+/// holds for blocks this policy itself moves off the device.
+pub struct QuarantinePolicy {
+    /// Devices currently quarantined, keyed by when quarantine began.
+    quarantined: HashMap<DiskId, SystemTime>,
+    /// `DeviceState::health` at or above this marks a device for quarantine.
+    threshold: f64,
+    /// Once health has dropped back below `threshold`, quarantine is only
+    /// lifted after this much time has passed since it began.
+    recovery: Duration,
+    /// The `migrate()` re-scan period.
+    interval: Duration,
+}
+
+impl QuarantinePolicy {
+    pub fn new(threshold: f64, recovery: Duration, interval: Duration) -> Self {
+        QuarantinePolicy {
+            quarantined: HashMap::new(),
+            threshold,
+            recovery,
+            interval,
+        }
+    }
+}
+
+impl PlacementPolicy for QuarantinePolicy {
+    fn init(
+        &mut self,
+        _devices: &HashMap<DiskId, DeviceState>,
+        _blocks: &HashMap<Block, DiskId>,
+        _subs: &mut SubscriptionManager,
+        now: SystemTime,
+    ) -> Box<dyn Iterator<Item = (SystemTime, Event)>> {
+        Box::new(
+            [(
+                now + self.interval,
+                Event::PlacementPolicy(PlacementMsg::Migrate),
+            )]
+            .into_iter(),
+        )
+    }
+
+    fn update(
+        &mut self,
+        msg: PlacementMsg,
+        devices: &mut HashMap<DiskId, DeviceState>,
+        blocks: &HashMap<Block, DiskId>,
+        now: SystemTime,
+        tx: &mut Sender<ResMsg>,
+    ) -> Box<dyn Iterator<Item = (SystemTime, Event)>> {
+        match msg {
+            PlacementMsg::Migrate => self.migrate(devices, blocks, now, tx),
+            _ => Box::new([].into_iter()),
+        }
+    }
+
+    fn migrate(
+        &mut self,
+        devices: &mut HashMap<DiskId, DeviceState>,
+        blocks: &HashMap<Block, DiskId>,
+        now: SystemTime,
+        tx: &mut Sender<ResMsg>,
+    ) -> Box<dyn Iterator<Item = (SystemTime, Event)>> {
+        for (id, state) in devices.iter() {
+            let is_quarantined = self.quarantined.contains_key(id);
+            if !is_quarantined && state.health >= self.threshold {
+                self.quarantined.insert(*id, now);
+                tx.send(ResMsg::Quarantine {
+                    now,
+                    device: *id,
+                    entered: true,
+                })
+                .unwrap();
+            } else if is_quarantined && state.health < self.threshold {
+                let quarantined_at = *self.quarantined.get(id).unwrap();
+                if now.duration_since(quarantined_at).unwrap_or(Duration::ZERO) >= self.recovery {
+                    self.quarantined.remove(id);
+                    tx.send(ResMsg::Quarantine {
+                        now,
+                        device: *id,
+                        entered: false,
+                    })
+                    .unwrap();
+                }
+            }
+        }
+
+        // Healthiest non-quarantined devices first, so drained blocks land
+        // on the best available destination.
+        let mut by_health = devices
+            .iter()
+            .filter(|(id, _)| !self.quarantined.contains_key(id))
+            .map(|(id, state)| (*id, state.health))
+            .collect::<Vec<_>>();
+        by_health.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap().then(a.0.cmp(&b.0)));
+        let by_health = by_health.into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+
+        let mut msgs = Vec::new();
+        let mut movements: HashMap<(DiskId, DiskId), usize> = HashMap::new();
+
+        for (block, disk) in blocks.iter() {
+            if !self.quarantined.contains_key(disk) {
+                continue;
+            }
+            // Re-checked every iteration (rather than once for the whole
+            // pass) so a target that fills up partway through this drain
+            // rotates to the next-healthiest device with room, instead of
+            // every remaining block piling onto the same, now-full target.
+            let Some(&target) = by_health.iter().find(|d| {
+                let state = devices.get(d).unwrap();
+                state.free >= state.footprint_estimate(1.0)
+            }) else {
+                continue;
+            };
+
+            let to_footprint = devices.get_mut(&target).unwrap().footprint(*block, 1.0);
+            devices.get_mut(&target).unwrap().free -= to_footprint;
+            let from_footprint = devices.get_mut(disk).unwrap().release_footprint(block);
+            devices.get_mut(disk).unwrap().free += from_footprint;
+
+            msgs.push((
+                now,
+                Event::Storage(crate::storage_stack::StorageMsg::Process(
+                    crate::storage_stack::Step::MoveInit(*block, target),
+                )),
+            ));
+            if devices.get(disk).unwrap().ssd {
+                msgs.push((
+                    now,
+                    Event::Storage(crate::storage_stack::StorageMsg::Process(
+                        crate::storage_stack::Step::Discard(*block, *disk),
+                    )),
+                ));
+            }
+            *movements.entry((*disk, target)).or_insert(0) += 1;
+        }
+
+        let moved = movements
+            .into_iter()
+            .map(|((from, to), size)| MovementInfo { from, to, size })
+            .collect();
+        tx.send(ResMsg::Policy {
+            now,
+            decided_at: now,
+            moved,
+        })
+        .unwrap();
+
+        Box::new(msgs.into_iter().chain([(
+            now + self.interval,
+            Event::PlacementPolicy(PlacementMsg::Migrate),
+        )]))
+    }
+}
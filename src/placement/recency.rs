@@ -0,0 +1,251 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+
+use crossbeam::channel::Sender;
+
+use crate::{
+    result_csv::{MovementInfo, ResMsg},
+    storage_stack::{DeviceAccessParams, DeviceState, DiskId},
+    Block, Event,
+};
+
+use super::{PlacementMsg, PlacementPolicy};
+
+/// A block's access history: `update()` stamps `last_fetched`/`last_written`
+/// as requests complete, `migrate()` stamps `last_migrated` once a move is
+/// issued for it.
+struct AccessLog {
+    last_fetched: SystemTime,
+    last_written: SystemTime,
+    last_migrated: SystemTime,
+}
+
+impl AccessLog {
+    fn new(now: SystemTime) -> Self {
+        AccessLog {
+            last_fetched: now,
+            last_written: now,
+            last_migrated: SystemTime::UNIX_EPOCH,
+        }
+    }
+
+    /// The more recent of the two accesses that make a block "hot"; age is
+    /// measured from here, not from `last_migrated`.
+    fn last_touch(&self) -> SystemTime {
+        self.last_fetched.max(self.last_written)
+    }
+}
+
+/// Ranks blocks purely by staleness instead of counting accesses like
+/// [`super::FrequencyPolicy`] or aging them in discrete eras like
+/// [`super::EraPolicy`]: a block untouched for longer than `cooldown` is a
+/// demotion candidate, one touched within the last `interval` is a promotion
+/// candidate, with no notion of "how often" in between.
+pub struct RecencyPolicy {
+    logs: HashMap<Block, AccessLog>,
+    /// Blocks untouched for longer than this are demoted to the slowest
+    /// device with free capacity.
+    cooldown: Duration,
+    /// Also the `migrate()` re-scan period: a block touched within the last
+    /// `interval` is a promotion candidate.
+    interval: Duration,
+    /// Caps how many blocks are promoted in a single `migrate()` pass.
+    promote_batch: usize,
+    /// Floor on how often `migrate()` actually does work: a call before
+    /// `last_pass + min_delay` has elapsed re-arms the next attempt but
+    /// skips the re-evaluation itself.
+    min_delay: Duration,
+    last_pass: SystemTime,
+}
+
+impl RecencyPolicy {
+    pub fn new(
+        cooldown: Duration,
+        interval: Duration,
+        promote_batch: usize,
+        min_delay: Duration,
+    ) -> Self {
+        RecencyPolicy {
+            logs: HashMap::new(),
+            cooldown,
+            interval,
+            promote_batch,
+            min_delay,
+            last_pass: SystemTime::UNIX_EPOCH,
+        }
+    }
+
+    /// Relative random-read cost, used purely to rank devices from fastest
+    /// to slowest. Mirrors the cost estimate `FrequencyPolicy`/`EraPolicy`
+    /// use for their own promote/demote decisions.
+    fn device_cost(devices: &HashMap<DiskId, DeviceState>, id: &DiskId) -> Duration {
+        let state = devices.get(id).unwrap();
+        state
+            .kind
+            .sample(&DeviceAccessParams::read(), state.current_queue_len())
+    }
+}
+
+impl PlacementPolicy for RecencyPolicy {
+    fn init(
+        &mut self,
+        _devices: &HashMap<DiskId, DeviceState>,
+        blocks: &HashMap<Block, DiskId>,
+        _subs: &mut crate::storage_stack::SubscriptionManager,
+        now: SystemTime,
+    ) -> Box<dyn Iterator<Item = (SystemTime, Event)>> {
+        for block in blocks.keys() {
+            self.logs.insert(*block, AccessLog::new(now));
+        }
+        Box::new(
+            [(
+                now + self.interval,
+                Event::PlacementPolicy(PlacementMsg::Migrate),
+            )]
+            .into_iter(),
+        )
+    }
+
+    fn update(
+        &mut self,
+        msg: PlacementMsg,
+        devices: &mut HashMap<DiskId, DeviceState>,
+        blocks: &HashMap<Block, DiskId>,
+        now: SystemTime,
+        tx: &mut Sender<ResMsg>,
+    ) -> Box<dyn Iterator<Item = (SystemTime, Event)>> {
+        match msg {
+            PlacementMsg::Migrate => return self.migrate(devices, blocks, now, tx),
+            PlacementMsg::Discarded(block) => {
+                self.logs.remove(&block);
+            }
+            PlacementMsg::Fetched(block) => {
+                self.logs.entry(block).or_insert_with(|| AccessLog::new(now)).last_fetched = now;
+            }
+            PlacementMsg::Written(block) => {
+                self.logs.entry(block).or_insert_with(|| AccessLog::new(now)).last_written = now;
+            }
+            PlacementMsg::MigrateComplete(block, _to_disk) => {
+                if let Some(log) = self.logs.get_mut(&block) {
+                    log.last_migrated = now;
+                }
+            }
+            PlacementMsg::DeviceEvent { .. } => {}
+        }
+        Box::new([].into_iter())
+    }
+
+    fn migrate(
+        &mut self,
+        devices: &mut HashMap<DiskId, DeviceState>,
+        blocks: &HashMap<Block, DiskId>,
+        now: SystemTime,
+        tx: &mut Sender<ResMsg>,
+    ) -> Box<dyn Iterator<Item = (SystemTime, Event)>> {
+        if now.duration_since(self.last_pass).unwrap_or(Duration::ZERO) < self.min_delay {
+            // Too soon since the last pass: keep the periodic cadence alive
+            // but skip the re-evaluation itself.
+            return Box::new(
+                [(
+                    now + self.interval,
+                    Event::PlacementPolicy(PlacementMsg::Migrate),
+                )]
+                .into_iter(),
+            );
+        }
+        self.last_pass = now;
+
+        // Rank every device fastest-to-slowest once per cycle; ties break
+        // toward the lower `DiskId` for determinism.
+        let mut by_speed = devices.keys().copied().collect::<Vec<_>>();
+        by_speed.sort_by(|a, b| {
+            Self::device_cost(devices, a)
+                .cmp(&Self::device_cost(devices, b))
+                .then(a.cmp(b))
+        });
+
+        let mut msgs = Vec::new();
+        let mut movements: HashMap<(DiskId, DiskId), usize> = HashMap::new();
+        let mut promoted = 0;
+
+        for (block, disk) in blocks.iter() {
+            let Some(log) = self.logs.get(block) else {
+                continue;
+            };
+            let age = now
+                .duration_since(log.last_touch())
+                .unwrap_or(Duration::ZERO);
+
+            let target = if age > self.cooldown {
+                // Only devices slower than the block's current one count, so
+                // a cold block is never moved up-tier.
+                by_speed
+                    .iter()
+                    .rev()
+                    .take_while(|d| **d != *disk)
+                    .find(|d| {
+                        devices.get(d).unwrap().free
+                            >= devices.get(d).unwrap().footprint_estimate(1.0)
+                    })
+                    .copied()
+            } else if age < self.interval && promoted < self.promote_batch {
+                by_speed.first().copied().filter(|fastest| {
+                    fastest != disk
+                        && devices.get(fastest).unwrap().free
+                            >= devices.get(fastest).unwrap().footprint_estimate(1.0)
+                })
+            } else {
+                None
+            };
+
+            let Some(target) = target else {
+                continue;
+            };
+            if age < self.interval {
+                promoted += 1;
+            }
+
+            let to_footprint = devices.get_mut(&target).unwrap().footprint(*block, 1.0);
+            devices.get_mut(&target).unwrap().free -= to_footprint;
+            let from_footprint = devices.get_mut(disk).unwrap().release_footprint(block);
+            devices.get_mut(disk).unwrap().free += from_footprint;
+
+            msgs.push((
+                now,
+                Event::Storage(crate::storage_stack::StorageMsg::Process(
+                    crate::storage_stack::Step::MoveInit(*block, target),
+                )),
+            ));
+            if let Some(log) = self.logs.get_mut(block) {
+                log.last_migrated = now;
+            }
+            if devices.get(disk).unwrap().ssd {
+                msgs.push((
+                    now,
+                    Event::Storage(crate::storage_stack::StorageMsg::Process(
+                        crate::storage_stack::Step::Discard(*block, *disk),
+                    )),
+                ));
+            }
+            *movements.entry((*disk, target)).or_insert(0) += 1;
+        }
+
+        let moved = movements
+            .into_iter()
+            .map(|((from, to), size)| MovementInfo { from, to, size })
+            .collect();
+        tx.send(ResMsg::Policy {
+            now,
+            decided_at: now,
+            moved,
+        })
+        .unwrap();
+
+        Box::new(msgs.into_iter().chain([(
+            now + self.interval,
+            Event::PlacementPolicy(PlacementMsg::Migrate),
+        )]))
+    }
+}
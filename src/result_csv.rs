@@ -9,7 +9,10 @@ use std::{
 use crossbeam::channel::{Receiver, Sender};
 use human_repr::HumanDuration;
 
-use crate::storage_stack::{DeviceState, DiskId};
+use crate::{
+    histogram::Histogram,
+    storage_stack::{DeviceState, DiskId},
+};
 
 /// This module collects data from different parts of the program and creates
 /// multiple csv files in the result directory. The results contain information
@@ -31,9 +34,32 @@ pub enum ResMsg {
     },
     Policy {
         now: SystemTime,
+        /// When this batch of moves was actually decided, distinct from
+        /// `now` (when it was reported) for migrations whose execution is
+        /// deferred past the decision point, e.g. `FrequencyPolicy`'s
+        /// in-flight queue. Equal to `now` for policies that decide and
+        /// dispatch in the same tick.
+        decided_at: SystemTime,
         /// Number of blocks moved in this iteration
         moved: Vec<MovementInfo>,
     },
+    /// Lifetime counters of the cache's spill tier, sent once at the end of
+    /// a run alongside `ResMsg::Device`.
+    Cache {
+        bytes_written: usize,
+        read_backs: usize,
+        hits: usize,
+        misses: usize,
+    },
+    /// A device crossed `QuarantinePolicy`'s health threshold in either
+    /// direction, so experiments can measure evacuation speed and quarantine
+    /// churn instead of only the migration traffic it causes.
+    Quarantine {
+        now: SystemTime,
+        device: DiskId,
+        /// `true` on entering quarantine, `false` on being lifted out of it.
+        entered: bool,
+    },
     Done,
 }
 
@@ -44,7 +70,7 @@ pub struct MovementInfo {
 }
 
 pub struct OpsInfo {
-    pub all: Vec<Duration>,
+    pub histogram: Histogram,
 }
 
 pub struct ResultCollector {
@@ -53,6 +79,8 @@ pub struct ResultCollector {
     devices: BufWriter<File>,
     sim: BufWriter<File>,
     policy: BufWriter<File>,
+    cache: BufWriter<File>,
+    quarantine: BufWriter<File>,
 }
 
 impl ResultCollector {
@@ -81,6 +109,18 @@ impl ResultCollector {
                 .write(true)
                 .open(path.join("policy.csv"))?,
         );
+        let cache = BufWriter::new(
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .open(path.join("cache.csv"))?,
+        );
+        let quarantine = BufWriter::new(
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .open(path.join("quarantine.csv"))?,
+        );
         let (tx, rx) = crossbeam::channel::unbounded();
         Ok((
             Self {
@@ -89,6 +129,8 @@ impl ResultCollector {
                 devices,
                 sim,
                 policy,
+                cache,
+                quarantine,
             },
             tx,
         ))
@@ -106,10 +148,14 @@ impl ResultCollector {
         }
         self.application.write(b"\n")?;
         self.devices.write_fmt(format_args!(
-            "id,total_requests,avg_latency_ns,max_latency_ns,idle_percentage\n"
+            "id,total_requests,avg_latency_ns,max_latency_ns,idle_percentage,avg_queue_occupancy,compression_ratio,codec_latency_ns\n"
         ))?;
 
-        self.policy.write(b"now,from,to,size\n")?;
+        self.policy
+            .write(b"now,from,to,size,decision_lag_us\n")?;
+        self.cache
+            .write(b"spill_bytes_written,spill_read_backs,spill_hit_rate\n")?;
+        self.quarantine.write(b"now,device,event\n")?;
 
         while let Ok(msg) = self.rx.recv() {
             match msg {
@@ -127,38 +173,17 @@ impl ResultCollector {
                         interval.as_secs_f32(),
                     ))?;
 
-                    for (idx, mut vals) in [writes, reads].into_iter().enumerate() {
-                        vals.all.sort();
-                        let total = vals.all.len() as u128;
-                        let avg = vals
-                            .all
-                            .iter()
-                            .map(|d| d.as_micros())
-                            .sum::<u128>()
-                            .checked_div(total)
-                            .unwrap_or(0);
-                        let max = vals.all.iter().map(|d| d.as_micros()).max().unwrap_or(0);
+                    for (idx, vals) in [writes, reads].into_iter().enumerate() {
+                        let hist = vals.histogram;
                         self.application.write_fmt(format_args!(
                             "{},{},{},{},{},{},{}",
-                            total,
-                            avg,
-                            max,
-                            vals.all
-                                .percentile(0.5)
-                                .unwrap_or(&Duration::ZERO)
-                                .as_micros(),
-                            vals.all
-                                .percentile(0.90)
-                                .unwrap_or(&Duration::ZERO)
-                                .as_micros(),
-                            vals.all
-                                .percentile(0.95)
-                                .unwrap_or(&Duration::ZERO)
-                                .as_micros(),
-                            vals.all
-                                .percentile(0.99)
-                                .unwrap_or(&Duration::ZERO)
-                                .as_micros(),
+                            hist.len(),
+                            hist.avg().as_micros(),
+                            hist.max().as_micros(),
+                            hist.quantile(0.5).as_micros(),
+                            hist.quantile(0.90).as_micros(),
+                            hist.quantile(0.95).as_micros(),
+                            hist.quantile(0.99).as_micros(),
                         ))?;
                         if idx != 1 {
                             self.application.write(b",")?;
@@ -171,16 +196,28 @@ impl ResultCollector {
                     let mut sorted_devices = map.iter().collect::<Vec<(&DiskId, &DeviceState)>>();
                     sorted_devices.sort_by(|x, y| x.1.name.cmp(&y.1.name));
                     for (_id, dev) in sorted_devices.iter() {
-                        let total = dev.total_req;
-                        let avg = dev.total_q.div_f32(total.clamp(1, usize::MAX) as f32);
-                        let max = dev.max_q;
+                        let total = dev.total_req();
+                        let avg = dev.total_q().div_f32(total.clamp(1, usize::MAX) as f32);
+                        let max = dev.max_q();
                         let free_blocks = dev.free;
                         let total_size = dev.total;
-                        let idle = (dev.idle_time.as_micros() / (total_runtime.as_micros() / 10000))
+                        let idle = (dev.idle_time().as_micros()
+                            / (total_runtime.as_micros() / 10000))
                             as f32
                             / 100f32;
+                        // Little's law: the average number of requests in the
+                        // system equals the total time requests spent in it
+                        // (queueing + service, `total_q`) divided by the
+                        // elapsed wall time.
+                        let avg_queue_occupancy =
+                            dev.total_q().as_secs_f64() / total_runtime.as_secs_f64();
+                        // Realized ratio the stack's own codec applies to
+                        // this device, or 1.0 (no savings) if it has none.
+                        let compression_ratio =
+                            dev.codec.map(|c| c.ratio).unwrap_or(1.0);
+                        let codec_latency_ns = dev.codec_latency_total.as_nanos();
                         self.devices.write_fmt(format_args!(
-                            "{},{total},{},{},{idle}\n",
+                            "{},{total},{},{},{idle},{avg_queue_occupancy},{compression_ratio},{codec_latency_ns}\n",
                             dev.name,
                             avg.as_nanos(),
                             max.as_nanos(),
@@ -205,11 +242,33 @@ impl ResultCollector {
                     self.sim
                         .write_fmt(format_args!("{}s\n", total_runtime.as_secs_f32()))?;
                 }
+                ResMsg::Cache {
+                    bytes_written,
+                    read_backs,
+                    hits,
+                    misses,
+                } => {
+                    let hit_rate = if hits + misses > 0 {
+                        hits as f64 / (hits + misses) as f64
+                    } else {
+                        0.0
+                    };
+                    self.cache
+                        .write_fmt(format_args!("{bytes_written},{read_backs},{hit_rate}\n"))?;
+                }
                 ResMsg::Done => break,
-                ResMsg::Policy { now, moved } => {
+                ResMsg::Policy {
+                    now,
+                    decided_at,
+                    moved,
+                } => {
+                    let decision_lag_us = now
+                        .duration_since(decided_at)
+                        .unwrap_or(Duration::ZERO)
+                        .as_micros();
                     for movement in moved {
                         self.policy.write_fmt(format_args!(
-                            "{},{},{},{}\n",
+                            "{},{},{},{},{decision_lag_us}\n",
                             now.duration_since(std::time::UNIX_EPOCH)
                                 .unwrap()
                                 .as_secs_f32(),
@@ -219,23 +278,25 @@ impl ResultCollector {
                         ))?;
                     }
                 }
+                ResMsg::Quarantine {
+                    now,
+                    device,
+                    entered,
+                } => {
+                    self.quarantine.write_fmt(format_args!(
+                        "{},{device},{}\n",
+                        now.duration_since(std::time::UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs_f32(),
+                        if entered { "enter" } else { "exit" },
+                    ))?;
+                }
             }
         }
         self.application.flush()?;
         self.devices.flush()?;
+        self.cache.flush()?;
+        self.quarantine.flush()?;
         self.sim.flush()
     }
 }
-
-trait Percentile<T> {
-    /// This function assuems that the given Vector is sorted.
-    fn percentile(&self, p: f32) -> Option<&T>;
-}
-
-impl<T> Percentile<T> for Vec<T> {
-    fn percentile(&self, p: f32) -> Option<&T> {
-        // should be sufficient for the determination of this percentile
-        let cut_off = (self.len() as f32 * p).ceil() as usize;
-        self.get(cut_off)
-    }
-}
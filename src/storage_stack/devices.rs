@@ -1,5 +1,5 @@
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     error::Error,
     path::Path,
     time::{Duration, SystemTime},
@@ -16,23 +16,29 @@ use super::DiskId;
 pub const BLOCK_SIZE_IN_MB: usize = 4;
 pub const BLOCK_SIZE_IN_B: usize = BLOCK_SIZE_IN_MB * 1024 * 1024;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Device(DeviceLatencyTable);
 
 #[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
-pub struct Parameters {
-    a: f64,
-    b: f64,
-    c: f64,
-    gap: f64,
+pub enum Parameters {
+    /// Curve fit to a generalized extreme value quantile function, as
+    /// produced by fitting the full latency distribution of a device.
+    Fitted { a: f64, b: f64, c: f64, gap: f64 },
+    /// A single measured average latency, sampled directly from a
+    /// `profile-device` benchmark sweep with no curve-fitting step. Every
+    /// percentile resolves to the same measured value.
+    Measured { avg_latency_ns: u64 },
 }
 
 impl Parameters {
     pub fn calculate(&self, percentile: f64) -> Duration {
-        Duration::from_nanos(
-            (std::f64::consts::E.powf(self.c)
-                * (self.a / ((percentile * self.gap) - 1.0)).powf(1.0 / self.b)) as u64,
-        )
+        match self {
+            Parameters::Fitted { a, b, c, gap } => Duration::from_nanos(
+                (std::f64::consts::E.powf(*c) * (*a / ((percentile * *gap) - 1.0)).powf(1.0 / *b))
+                    as u64,
+            ),
+            Parameters::Measured { avg_latency_ns } => Duration::from_nanos(*avg_latency_ns),
+        }
     }
 
     pub fn sample<R: rand::Rng>(&self, rng: &mut R) -> Duration {
@@ -60,13 +66,80 @@ impl Default for Device {
 }
 
 impl Device {
-    pub fn sample(&self, access: &DeviceAccessParams) -> Duration {
+    /// Sample a latency for `access` at the given live `queue_depth`. The
+    /// CSV-loaded table is keyed by queue depth precisely because latency
+    /// grows with concurrency, so rather than always looking at a single
+    /// hardcoded depth, the two tabulated rows (same block size/op) bracketing
+    /// `queue_depth` are found and the sampled duration is linearly
+    /// interpolated between them. Falls back to the nearest available row at
+    /// the extremes, or the single tabulated row if only one depth exists.
+    pub fn sample(&self, access: &DeviceAccessParams, queue_depth: usize) -> Duration {
         let mut rng = rand::thread_rng();
-        let pct = rand::distributions::Uniform::new(0.0, 1.0).sample(&mut rng);
-        self.0 .0.get(access).unwrap().calculate(pct)
+        let pct: f64 = rand::distributions::Uniform::new(0.0, 1.0).sample(&mut rng);
+
+        let mut rows: Vec<(u32, &Parameters)> = self
+            .0
+             .0
+            .iter()
+            .filter(|(k, _)| {
+                k.block_size == access.block_size && k.rw == access.rw && k.op == access.op
+            })
+            .map(|(k, p)| (k.queue_depth, p))
+            .collect();
+        rows.sort_by_key(|(depth, _)| *depth);
+
+        let depth = queue_depth as u32;
+        match rows.as_slice() {
+            [] => panic!("No latency table entry matching access {:?}", access),
+            [(_, only)] => only.calculate(pct),
+            rows if depth <= rows[0].0 => rows[0].1.calculate(pct),
+            rows if depth >= rows[rows.len() - 1].0 => rows[rows.len() - 1].1.calculate(pct),
+            rows => {
+                let hi_idx = rows.partition_point(|(d, _)| *d <= depth);
+                let (lo_depth, lo_params) = rows[hi_idx - 1];
+                let (hi_depth, hi_params) = rows[hi_idx];
+                let lo_dur = lo_params.calculate(pct);
+                let hi_dur = hi_params.calculate(pct);
+                let t = (depth - lo_depth) as f64 / (hi_depth - lo_depth) as f64;
+                lo_dur + Duration::from_secs_f64((hi_dur.as_secs_f64() - lo_dur.as_secs_f64()) * t)
+            }
+        }
+    }
+}
+
+/// One of a device's independent request queues, modelling e.g. one queue
+/// pair of a multi-queue NVMe controller. Each queue tracks its own depth and
+/// reservation horizon, so accesses routed to different queues can be
+/// in flight on the same device at once.
+#[derive(Serialize, Deserialize)]
+pub struct DeviceQueue {
+    pub reserved_until: SystemTime,
+    pub can_requeue_at: SystemTime,
+    pub max_queue_len: usize,
+    pub current_queue_len: usize,
+    pub idle_time: Duration,
+    // Metrics, scoped to this queue alone.
+    pub max_q: Duration,
+    pub total_q: Duration,
+    pub total_req: usize,
+}
+
+impl DeviceQueue {
+    pub fn new(max_queue_len: usize, now: SystemTime) -> Self {
+        DeviceQueue {
+            reserved_until: now,
+            can_requeue_at: now,
+            max_queue_len,
+            current_queue_len: 0,
+            idle_time: Duration::ZERO,
+            max_q: Duration::ZERO,
+            total_q: Duration::ZERO,
+            total_req: 0,
+        }
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct DeviceState {
     pub name: String,
     pub kind: Device,
@@ -74,21 +147,373 @@ pub struct DeviceState {
     pub free: usize,
     // Absolute number of blocks which can be stored.
     pub total: usize,
-    pub reserved_until: SystemTime,
     // pub submission_queue: VecDeque<(SystemTime, Access, Option<DiskId>)>,
-    pub max_queue_len: usize,
-    pub current_queue_len: usize,
-    // Metrics
-    pub max_q: Duration,
-    pub total_q: Duration,
-    pub total_req: usize,
-    pub idle_time: Duration,
+    pub queues: Vec<DeviceQueue>,
+    // How the next access picks a queue out of `queues`.
+    pub scheduler: QueueScheduler,
+    // Present iff this device is over-committed: `total` is a logical
+    // capacity larger than what `thin` actually backs.
+    pub thin: Option<ThinProvisioning>,
+    // Which queue an in-flight block's access was submitted to, so its
+    // `Finish` can be routed back to the right queue without threading a
+    // queue index through `Access`/`StorageMsg` themselves.
+    pub in_flight: HashMap<Block, usize>,
+    // Optional cap on provisioned IOPS/throughput, e.g. cloud block volumes or
+    // cgroup-throttled disks.
+    pub rate_limiter: Option<RateLimiter>,
+    // Present iff this tier compresses data it stores: placement policies
+    // charge a block moved here through `footprint()` (whole slots, with the
+    // fractional savings banked in `footprint_carry`) and add a decompression
+    // penalty to their own cost estimate for reading it back. The storage
+    // stack itself charges no extra latency for it (see `codec`).
+    pub compressing: Option<f32>,
+    // Marks a flash/SSD-class device, subject to GC write amplification:
+    // `stale` tracks physical space occupied by data that's no longer live
+    // but hasn't been reclaimed via an explicit discard/TRIM yet.
+    pub ssd: bool,
+    pub stale: usize,
+    // Present iff the storage stack itself applies a fixed-ratio codec to
+    // every access on this device (as opposed to `compressing`, which only
+    // feeds a placement policy's own per-block migration cost estimate):
+    // every write pays `encode_latency`, every read `decode_latency`.
+    pub codec: Option<Codec>,
+    // Total encode/decode latency `codec` has charged so far.
+    pub codec_latency_total: Duration,
+    // Rising error rate/latency signal in `0.0..=1.0`, `0.0` being fully
+    // healthy. Nothing in this simulator drives it yet (no fault-injection
+    // model exists), but a `placement::QuarantinePolicy` watching it can
+    // evacuate a device once it crosses a threshold.
+    pub health: f64,
+    // Fractional compression savings from `footprint()` not yet reflected
+    // as a whole `free`/`total` slot. A single block's `ceil` always rounds
+    // up to 1 regardless of `ratio`, so without this a ratio below `1.0`
+    // would never save any capacity at all; instead each call banks
+    // `1.0 - ratio` here and skips the next slot's charge once enough has
+    // accumulated, so savings show up over many blocks instead of none.
+    pub footprint_carry: f64,
+    // Same accumulator as `footprint_carry`, but for `codec_footprint()`'s
+    // fixed per-device `codec.ratio` instead of a per-block ratio.
+    pub codec_footprint_carry: f64,
+    // What `footprint()` actually charged for each block currently resident
+    // here, so `release_footprint()` can credit back exactly that amount
+    // instead of re-deriving it from `footprint_carry`, which has moved on
+    // to charging whatever arrived (or departed) since. Absent entries (a
+    // block placed before this tracking existed, e.g. at config load) credit
+    // a full slot back, the conservative assumption `footprint_carry` itself
+    // starts from.
+    pub resident_footprint: HashMap<Block, usize>,
 }
 
-#[derive(Debug, Clone, Default)]
+impl DeviceState {
+    /// Total idle time across all of this device's queues.
+    pub fn idle_time(&self) -> Duration {
+        self.queues.iter().map(|q| q.idle_time).sum()
+    }
+
+    /// Total number of requests currently in flight across all queues.
+    pub fn current_queue_len(&self) -> usize {
+        self.queues.iter().map(|q| q.current_queue_len).sum()
+    }
+
+    /// Total number of requests ever submitted, summed across all queues.
+    pub fn total_req(&self) -> usize {
+        self.queues.iter().map(|q| q.total_req).sum()
+    }
+
+    /// Total accumulated queueing delay, summed across all queues.
+    pub fn total_q(&self) -> Duration {
+        self.queues.iter().map(|q| q.total_q).sum()
+    }
+
+    /// Worst single-request queueing delay seen on any of this device's queues.
+    pub fn max_q(&self) -> Duration {
+        self.queues
+            .iter()
+            .map(|q| q.max_q)
+            .max()
+            .unwrap_or(Duration::ZERO)
+    }
+
+    /// How many `free`/`total` slots a block with the given compressibility
+    /// `ratio` (fraction of its original size retained after compression)
+    /// would occupy if stored here, without committing to it: `1` on a
+    /// non-compressing tier, or `0`/`1` on a compressing one depending on
+    /// `footprint_carry` (see its doc comment). Used for admission checks
+    /// that may not end up placing anything; see [`Self::footprint`] for the
+    /// version that actually charges it against a specific block.
+    pub fn footprint_estimate(&self, ratio: f32) -> usize {
+        match self.compressing {
+            Some(_) => {
+                if self.footprint_carry + (1.0 - ratio as f64) >= 1.0 {
+                    0
+                } else {
+                    1
+                }
+            }
+            None => 1,
+        }
+    }
+
+    /// Commit [`Self::footprint_estimate`] as the charge for `block` actually
+    /// arriving here, banking the exact slot count in `resident_footprint` so
+    /// a later [`Self::release_footprint`] call can credit back precisely
+    /// what was charged instead of re-deriving it from `footprint_carry`,
+    /// which by then may have moved on to charging whatever else has arrived
+    /// (or departed) since.
+    pub fn footprint(&mut self, block: Block, ratio: f32) -> usize {
+        let slots = match self.compressing {
+            Some(_) => {
+                self.footprint_carry += 1.0 - ratio as f64;
+                if self.footprint_carry >= 1.0 {
+                    self.footprint_carry -= 1.0;
+                    0
+                } else {
+                    1
+                }
+            }
+            None => 1,
+        };
+        self.resident_footprint.insert(block, slots);
+        slots
+    }
+
+    /// Credit back exactly what [`Self::footprint`] charged when `block`
+    /// last arrived here. Blocks resident from before this tracking existed
+    /// (e.g. placed directly at config load) have no entry and credit a full
+    /// slot, the conservative assumption `footprint_carry` itself starts from.
+    pub fn release_footprint(&mut self, block: &Block) -> usize {
+        self.resident_footprint.remove(block).unwrap_or(1)
+    }
+
+    /// How many `free`/`total` slots a block occupies if stored here, per
+    /// this device's own fixed `codec` ratio (`1` if it has none). Unlike
+    /// [`Self::footprint`], which takes a per-block ratio supplied by a
+    /// placement policy, this always uses the device's configured ratio;
+    /// see `codec_footprint_carry` for why it isn't always `1`.
+    ///
+    /// Doesn't need `footprint`'s per-resident-block tracking: its only
+    /// caller (`StorageStack::insert`) charges a block once on arrival, and
+    /// nothing ever calls this again for that same block to credit it back
+    /// (discards release a flat one slot regardless of codec, a separate,
+    /// pre-existing inconsistency in `StorageStack`'s discard handling). With
+    /// a single charging call site and no paired credit to keep exact, there
+    /// is nothing for resident-block tracking to pair up.
+    pub fn codec_footprint(&mut self) -> usize {
+        match self.codec {
+            Some(codec) => {
+                self.codec_footprint_carry += 1.0 - codec.ratio as f64;
+                if self.codec_footprint_carry >= 1.0 {
+                    self.codec_footprint_carry -= 1.0;
+                    0
+                } else {
+                    1
+                }
+            }
+            None => 1,
+        }
+    }
+
+    /// Fraction of this device's capacity currently tied up as stale,
+    /// unreclaimed garbage. Zero on non-SSD devices.
+    pub fn stale_fraction(&self) -> f32 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.stale as f32 / self.total as f32
+        }
+    }
+
+    /// Random-write cost multiplier from GC write amplification: grows
+    /// linearly with the stale fraction, so a device thrashing between
+    /// migrations degrades until something discards the garbage it left
+    /// behind.
+    pub fn write_amplification(&self) -> f32 {
+        1.0 + self.stale_fraction()
+    }
+
+    /// Index of the queue with the fewest in-flight requests, for
+    /// least-loaded submission of the next access.
+    pub fn least_loaded_queue(&self) -> usize {
+        self.queues
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, q)| q.current_queue_len)
+            .map(|(i, _)| i)
+            .expect("a device always has at least one queue")
+    }
+
+    /// Pick the queue the next access should be routed to, according to
+    /// `self.scheduler`.
+    pub fn select_queue(&mut self) -> usize {
+        match &mut self.scheduler {
+            QueueScheduler::LeastLoaded => self
+                .queues
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, q)| q.current_queue_len)
+                .map(|(i, _)| i)
+                .expect("a device always has at least one queue"),
+            QueueScheduler::RoundRobin { next } => {
+                let idx = *next % self.queues.len();
+                *next = (*next + 1) % self.queues.len();
+                idx
+            }
+            QueueScheduler::WeightedDeadline { weights } => self
+                .queues
+                .iter()
+                .enumerate()
+                .min_by(|(ia, a), (ib, b)| {
+                    let score_a = a.current_queue_len as f64 / weights.get(*ia).copied().unwrap_or(1.0);
+                    let score_b = b.current_queue_len as f64 / weights.get(*ib).copied().unwrap_or(1.0);
+                    score_a.partial_cmp(&score_b).unwrap()
+                })
+                .map(|(i, _)| i)
+                .expect("a device always has at least one queue"),
+        }
+    }
+}
+
+/// Selects which of a device's [`DeviceQueue`]s the next access is routed to.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum QueueScheduler {
+    /// Always pick the queue with the fewest requests currently in flight.
+    LeastLoaded,
+    /// Rotate through queues in order, ignoring current load.
+    RoundRobin { next: usize },
+    /// Like [`QueueScheduler::LeastLoaded`], but each queue's in-flight count
+    /// is scaled down by its weight first, so a higher-weighted queue is
+    /// treated as less busy and absorbs a proportionally larger share of
+    /// requests (e.g. to model a faster queue pair pinned to a dedicated
+    /// CPU core).
+    WeightedDeadline { weights: Vec<f64> },
+}
+
+impl Default for QueueScheduler {
+    fn default() -> Self {
+        QueueScheduler::LeastLoaded
+    }
+}
+
+/// A fixed-ratio compression codec applied by the storage stack to every
+/// access on a device, e.g. a cold tier backed by zstd/lz4. `ratio` is the
+/// fraction of a block's original size retained after compression.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct Codec {
+    pub ratio: f32,
+    pub encode_latency: Duration,
+    pub decode_latency: Duration,
+}
+
+/// Over-commitment state for a thin-provisioned device: the advertised
+/// logical capacity (`DeviceState::total`) exceeds the real backing store, so
+/// a logical block only consumes physical space on its first write. `Block`
+/// is used both as the logical identifier and, implicitly, as the unit of
+/// physical allocation (no separate remap table is kept).
+#[derive(Serialize, Deserialize)]
+pub struct ThinProvisioning {
+    // Real backing size, distinct from the advertised logical `total`.
+    pub physical_total: usize,
+    pub physical_free: usize,
+    // Extra latency charged once per block, on top of the normal write
+    // latency, for the metadata lookup/update a lazy allocation requires.
+    pub alloc_latency: Duration,
+    // Logical blocks that have already been allocated physical space.
+    pub allocated: HashSet<Block>,
+}
+
+impl ThinProvisioning {
+    pub fn new(physical_capacity: usize, alloc_latency: Duration) -> Self {
+        ThinProvisioning {
+            physical_total: physical_capacity,
+            physical_free: physical_capacity,
+            alloc_latency,
+            allocated: HashSet::new(),
+        }
+    }
+}
+
+/// Configuration for a per-device [`RateLimiter`], loaded alongside the device
+/// definition. IOPS are counted per access, bandwidth in bytes per second.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct RateLimiterConfig {
+    pub iops: f64,
+    pub iops_burst: f64,
+    pub bandwidth_bps: f64,
+    pub bandwidth_burst: f64,
+}
+
+/// A single token bucket: holds up to `capacity` tokens and refills
+/// continuously at `rate` tokens per second.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+struct TokenBucket {
+    capacity: f64,
+    rate: f64,
+    level: f64,
+    last_refill: SystemTime,
+}
+
+impl TokenBucket {
+    fn new(capacity: f64, rate: f64, now: SystemTime) -> Self {
+        TokenBucket {
+            capacity,
+            rate,
+            level: capacity,
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, now: SystemTime) {
+        if let Ok(elapsed) = now.duration_since(self.last_refill) {
+            self.level = (self.level + self.rate * elapsed.as_secs_f64()).min(self.capacity);
+            self.last_refill = now;
+        }
+    }
+
+    /// How long to wait, from `last_refill`, until `cost` tokens are available.
+    fn wait_for(&self, cost: f64) -> Duration {
+        if self.level >= cost {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((cost - self.level) / self.rate)
+        }
+    }
+}
+
+/// Gates device accesses on two independent token buckets, one counting
+/// operations and one counting bytes (in units of [`BLOCK_SIZE_IN_B`]).
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct RateLimiter {
+    ops: TokenBucket,
+    bytes: TokenBucket,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimiterConfig, now: SystemTime) -> Self {
+        RateLimiter {
+            ops: TokenBucket::new(config.iops_burst, config.iops, now),
+            bytes: TokenBucket::new(config.bandwidth_burst, config.bandwidth_bps, now),
+        }
+    }
+
+    /// Reserve one access of `bytes` size, returning the point in time at
+    /// which both buckets hold enough tokens (`now` itself if already available).
+    pub fn reserve(&mut self, now: SystemTime, bytes: f64) -> SystemTime {
+        self.ops.refill(now);
+        self.bytes.refill(now);
+        let wait = self.ops.wait_for(1.0).max(self.bytes.wait_for(bytes));
+        let ready_at = now + wait;
+        self.ops.refill(ready_at);
+        self.bytes.refill(ready_at);
+        self.ops.level -= 1.0;
+        self.bytes.level -= bytes;
+        ready_at
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DeviceLatencyTable(HashMap<DeviceAccessParams, Parameters>);
 
-#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+#[derive(Clone, Debug, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub struct DeviceAccessParams {
     block_size: u32,
     queue_depth: u32,
@@ -116,6 +541,33 @@ impl DeviceAccessParams {
             op: Op::Read,
         }
     }
+
+    pub fn flush() -> Self {
+        DeviceAccessParams {
+            block_size: BLOCK_SIZE_IN_B as u32,
+            queue_depth: 128,
+            rw: (1.0f32).to_bits(),
+            op: Op::Flush,
+        }
+    }
+
+    pub fn discard() -> Self {
+        DeviceAccessParams {
+            block_size: BLOCK_SIZE_IN_B as u32,
+            queue_depth: 128,
+            rw: (1.0f32).to_bits(),
+            op: Op::Discard,
+        }
+    }
+
+    pub fn write_zeroes() -> Self {
+        DeviceAccessParams {
+            block_size: BLOCK_SIZE_IN_B as u32,
+            queue_depth: 128,
+            rw: (1.0f32).to_bits(),
+            op: Op::WriteZeroes,
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -141,7 +593,7 @@ impl DeviceRecord {
     }
 
     fn to_params(&self) -> Parameters {
-        Parameters {
+        Parameters::Fitted {
             a: self.a,
             b: self.b,
             c: self.c,
@@ -181,13 +633,87 @@ pub fn load_devices(
     Ok(devices)
 }
 
-#[derive(Deserialize, Debug, Hash, Clone, Eq, PartialEq)]
+/// One row of a `profile-device` benchmark sweep, as written to its
+/// `--result-path` CSV: measured average latency and bandwidth at a given
+/// block size/queue depth, with no curve-fitting step applied.
+#[derive(Deserialize)]
+pub struct RawProfileRecord {
+    block_size: u32,
+    #[allow(dead_code)]
+    blocks: u64,
+    avg_latency_us: u64,
+    #[allow(dead_code)]
+    bandwidth_mibs: f64,
+    op: Op,
+    pattern: String,
+    queue_depth: u32,
+}
+
+impl RawProfileRecord {
+    fn to_access_params(&self) -> DeviceAccessParams {
+        DeviceAccessParams {
+            block_size: self.block_size,
+            queue_depth: self.queue_depth,
+            rw: (1.0f32).to_bits(),
+            op: self.op.clone(),
+        }
+    }
+
+    fn to_params(&self) -> Parameters {
+        Parameters::Measured {
+            avg_latency_ns: self.avg_latency_us * 1_000,
+        }
+    }
+}
+
+/// Like [`load_devices`], but reads a directory of raw `profile-device`
+/// benchmark results instead of pre-fitted curves, letting a simulation run
+/// directly against measured latencies. `DeviceAccessParams` has no notion of
+/// a sequential/random access pattern (the fitted-curve format doesn't carry
+/// one either), so only the `random` rows of the sweep are kept.
+pub fn load_raw_profiles(
+    path: impl AsRef<Path>,
+) -> Result<HashMap<String, DeviceLatencyTable>, Box<dyn Error>> {
+    let mut devices = HashMap::new();
+    for file in std::fs::read_dir(path)? {
+        let file = file?;
+        if file.path().is_file() {
+            let mut device = DeviceLatencyTable::default();
+            for record in csv::Reader::from_path(file.path())?.deserialize::<RawProfileRecord>() {
+                let record = record?;
+                if record.pattern != "random" {
+                    continue;
+                }
+                device
+                    .0
+                    .insert(record.to_access_params(), record.to_params());
+            }
+            devices.insert(
+                file.path()
+                    .file_stem()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string(),
+                device,
+            );
+        }
+    }
+    Ok(devices)
+}
+
+#[derive(Deserialize, Serialize, Debug, Hash, Clone, Eq, PartialEq)]
 #[repr(u8)]
 pub enum Op {
     #[serde(rename = "write")]
     Write = 0,
     #[serde(rename = "read")]
     Read,
+    #[serde(rename = "flush")]
+    Flush,
+    #[serde(rename = "discard")]
+    Discard,
+    #[serde(rename = "write_zeroes")]
+    WriteZeroes,
 }
 
 // #[derive(Deserialize)]
@@ -1,13 +1,20 @@
-use std::{collections::HashMap, fmt::Display, time::SystemTime};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::{BufReader, BufWriter},
+    path::Path,
+    time::SystemTime,
+};
 
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::{
     cache::{CacheLogic, CacheMsg},
-    Access, Block, Event,
+    Access, Block, Event, SimError,
 };
 
-#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct DiskId(pub usize);
 
 impl Display for DiskId {
@@ -22,6 +29,7 @@ pub struct StorageStack<S> {
     pub cache: CacheLogic,
     pub state: S,
     pub blocks_on_hold: HashMap<Block, SystemTime>,
+    pub subscriptions: SubscriptionManager,
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -29,6 +37,19 @@ pub enum StorageMsg {
     Init(Access),
     Finish(Access),
     Process(Step),
+    Maintenance(MaintenanceOp),
+}
+
+/// Housekeeping operations issued directly against the storage stack, outside
+/// the regular cache/application read-write path.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum MaintenanceOp {
+    /// Barrier on a device: waits for all currently queued writes to complete.
+    Flush(DiskId),
+    /// Trim a block, freeing its capacity back to the device.
+    Discard(Block),
+    /// Zero a block's contents without reading it back from storage.
+    WriteZeroes(Block),
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -36,13 +57,23 @@ pub enum Step {
     MoveInit(Block, DiskId),
     MoveReadFinished(Block, DiskId),
     MoveWriteFinished(Block),
+    /// `block` just vacated `DiskId` (it was migrated elsewhere): the
+    /// physical space it occupied there is now garbage until reclaimed,
+    /// unlike a plain `free` increment which says nothing to the device
+    /// itself. SSD-class devices track this to model GC write amplification.
+    Discard(Block, DiskId),
 }
 
 mod devices;
 pub use devices::{
-    load_devices, to_device, Device, DeviceAccessParams, DeviceLatencyTable, DeviceState,
+    load_devices, load_raw_profiles, to_device, Codec, Device, DeviceAccessParams,
+    DeviceLatencyTable, DeviceQueue, DeviceState, RateLimiter, RateLimiterConfig, ThinProvisioning,
+    BLOCK_SIZE_IN_B,
 };
 
+mod subscription;
+pub use subscription::{DeviceEventKind, SubscriptionManager};
+
 #[derive(Error, Debug)]
 pub enum StorageError {
     #[error("Could not find block {block:?}")]
@@ -51,9 +82,30 @@ pub enum StorageError {
     BlockIsBusy { block: Block, msg: StorageMsg },
     #[error("Could not find device {id}")]
     InvalidDevice { id: DiskId },
+    #[error("Device {id} has no physical space left for a new thin-provisioned allocation")]
+    OutOfSpace { id: DiskId },
 }
 
 impl<S> StorageStack<S> {
+    /// Re-check `dev`'s registered capacity thresholds, turning any that just
+    /// crossed into `PlacementMsg::DeviceEvent`s for the main loop to insert.
+    fn check_subscriptions(&mut self, dev: DiskId, now: SystemTime) -> Vec<(SystemTime, Event)> {
+        let state = self.devices.get(&dev).unwrap();
+        self.subscriptions
+            .check(dev, state)
+            .into_iter()
+            .map(|kind| {
+                (
+                    now,
+                    Event::PlacementPolicy(crate::placement::PlacementMsg::DeviceEvent {
+                        device: dev,
+                        kind,
+                    }),
+                )
+            })
+            .collect()
+    }
+
     /// Act on specified block and return subsequent event.
     pub fn process(
         &mut self,
@@ -76,17 +128,16 @@ impl<S> StorageStack<S> {
                 self.queue_access(&access, now, None)
             }
             StorageMsg::Finish(access) => {
-                self.finish_access(&access, now);
-                Ok(Box::new(
-                    [(
-                        now,
-                        Event::PlacementPolicy(match access {
-                            Access::Read(b) => crate::placement::PlacementMsg::Fetched(b),
-                            Access::Write(b) => crate::placement::PlacementMsg::Written(b),
-                        }),
-                    )]
-                    .into_iter(),
-                ))
+                let mut events = self.finish_access(&access, now);
+                events.push((
+                    now,
+                    Event::PlacementPolicy(match access {
+                        Access::Read(b) => crate::placement::PlacementMsg::Fetched(b),
+                        Access::Write(b) => crate::placement::PlacementMsg::Written(b),
+                        Access::Discard(b) => crate::placement::PlacementMsg::Discarded(b),
+                    }),
+                ));
+                Ok(Box::new(events.into_iter()))
             }
             StorageMsg::Process(ref step) => match step {
                 Step::MoveReadFinished(block, to_disk) => {
@@ -113,17 +164,132 @@ impl<S> StorageStack<S> {
                     self.finish_access(&Access::Write(*block), now);
                     Ok(Box::new([].into_iter()))
                 }
+                Step::Discard(_block, disk) => {
+                    let dev_stats = self
+                        .devices
+                        .get_mut(disk)
+                        .ok_or(StorageError::InvalidDevice { id: *disk })?;
+                    if dev_stats.ssd {
+                        dev_stats.stale += 1;
+                    }
+                    Ok(Box::new([].into_iter()))
+                }
             },
+            StorageMsg::Maintenance(ref op) => self.queue_maintenance(op, now),
         }
     }
 
-    fn finish_access(&mut self, access: &Access, now: SystemTime) -> () {
-        let dev = self
-            .devices
-            .get_mut(self.blocks.get(access.block()).unwrap())
-            .unwrap();
-        assert!(dev.current_queue_len > 0);
-        dev.current_queue_len -= 1;
+    fn queue_maintenance(
+        &mut self,
+        op: &MaintenanceOp,
+        mut now: SystemTime,
+    ) -> Result<Box<dyn Iterator<Item = (SystemTime, Event)>>, StorageError> {
+        match *op {
+            MaintenanceOp::Flush(disk) => {
+                let dev_stats = self
+                    .devices
+                    .get_mut(&disk)
+                    .ok_or(StorageError::InvalidDevice { id: disk })?;
+                // Force the barrier to wait for all writes currently queued on
+                // any of the device's queues before it can itself complete.
+                let reserved_until = dev_stats
+                    .queues
+                    .iter()
+                    .map(|q| q.reserved_until)
+                    .max()
+                    .expect("a device always has at least one queue");
+                now = now.max(reserved_until);
+                let until = now
+                    + dev_stats
+                        .kind
+                        .sample(&DeviceAccessParams::flush(), dev_stats.current_queue_len());
+                for queue in dev_stats.queues.iter_mut() {
+                    queue.reserved_until = queue.reserved_until.max(until);
+                    queue.total_req += 1;
+                }
+                Ok(Box::new([].into_iter()))
+            }
+            MaintenanceOp::Discard(block) => {
+                let dev = *self
+                    .blocks
+                    .get(&block)
+                    .ok_or(StorageError::InvalidBlock { block })?;
+                let dev_stats = self
+                    .devices
+                    .get_mut(&dev)
+                    .ok_or(StorageError::InvalidDevice { id: dev })?;
+                let queue_idx = dev_stats.select_queue();
+                let until = now
+                    + dev_stats
+                        .kind
+                        .sample(&DeviceAccessParams::discard(), dev_stats.current_queue_len());
+                let queue = &mut dev_stats.queues[queue_idx];
+                queue.reserved_until = queue.reserved_until.max(until);
+                queue.total_req += 1;
+                dev_stats.free += 1;
+                dev_stats.stale = dev_stats.stale.saturating_sub(1);
+                if let Some(thin) = dev_stats.thin.as_mut() {
+                    if thin.allocated.remove(&block) {
+                        thin.physical_free += 1;
+                    }
+                }
+                Ok(Box::new(self.check_subscriptions(dev, now).into_iter()))
+            }
+            MaintenanceOp::WriteZeroes(block) => {
+                let dev = *self
+                    .blocks
+                    .get(&block)
+                    .ok_or(StorageError::InvalidBlock { block })?;
+                let dev_stats = self
+                    .devices
+                    .get_mut(&dev)
+                    .ok_or(StorageError::InvalidDevice { id: dev })?;
+                let queue_idx = dev_stats.select_queue();
+                now = now.max(dev_stats.queues[queue_idx].can_requeue_at);
+                let until = now
+                    + dev_stats.kind.sample(
+                        &DeviceAccessParams::write_zeroes(),
+                        dev_stats.current_queue_len(),
+                    );
+                let queue = &mut dev_stats.queues[queue_idx];
+                queue.reserved_until = queue.reserved_until.max(until);
+                queue.current_queue_len += 1;
+                if queue.current_queue_len >= queue.max_queue_len {
+                    queue.can_requeue_at = until;
+                }
+                queue.total_req += 1;
+                dev_stats.in_flight.insert(block, queue_idx);
+                Ok(Box::new(
+                    [(
+                        until,
+                        Event::Storage(StorageMsg::Finish(Access::Write(block))),
+                    )]
+                    .into_iter(),
+                ))
+            }
+        }
+    }
+
+    fn finish_access(&mut self, access: &Access, now: SystemTime) -> Vec<(SystemTime, Event)> {
+        let disk = *self.blocks.get(access.block()).unwrap();
+        let dev = self.devices.get_mut(&disk).unwrap();
+        let queue_idx = dev
+            .in_flight
+            .remove(access.block())
+            .expect("block must have been queued through this device's queues");
+        let queue = &mut dev.queues[queue_idx];
+        assert!(queue.current_queue_len > 0);
+        queue.current_queue_len -= 1;
+        if let Access::Discard(block) = access {
+            dev.free += 1;
+            if let Some(thin) = dev.thin.as_mut() {
+                if thin.allocated.remove(block) {
+                    thin.physical_free += 1;
+                }
+            }
+            return self.check_subscriptions(disk, now);
+        }
+        Vec::new()
     }
 
     fn queue_access(
@@ -143,31 +309,74 @@ impl<S> StorageStack<S> {
             .get_mut(dev)
             .ok_or(StorageError::InvalidDevice { id: dev.clone() })?;
 
+        // A thin-provisioned device only backs a block with physical space on
+        // its first write; later reads/writes to the same block are normal.
+        let mut alloc_latency = std::time::Duration::ZERO;
+        if let (Access::Write(block), Some(thin)) = (access, dev_stats.thin.as_mut()) {
+            if !thin.allocated.contains(block) {
+                if thin.physical_free == 0 {
+                    return Err(StorageError::OutOfSpace { id: *dev });
+                }
+                thin.physical_free -= 1;
+                thin.allocated.insert(*block);
+                alloc_latency = thin.alloc_latency;
+            }
+        }
+
         let origin = now;
-        now = now.max(dev_stats.can_requeue_at);
+        let queue_idx = dev_stats.select_queue();
+        now = now.max(dev_stats.queues[queue_idx].can_requeue_at);
+        if let Some(limiter) = dev_stats.rate_limiter.as_mut() {
+            now = limiter.reserve(now, BLOCK_SIZE_IN_B as f64);
+        }
 
         // Enqueue and immediately submit request
+        let total_queue_len = dev_stats.current_queue_len();
+        // A codec'd device pays to encode every write and decode every read,
+        // whether the access came from the application or is one leg of a
+        // migration (`queue_access` backs both).
+        let codec_latency = dev_stats
+            .codec
+            .as_ref()
+            .map(|codec| match access {
+                Access::Write(_) => codec.encode_latency,
+                Access::Read(_) => codec.decode_latency,
+                Access::Discard(_) => Duration::ZERO,
+            })
+            .unwrap_or(Duration::ZERO);
+        dev_stats.codec_latency_total += codec_latency;
         let until = now
+            + alloc_latency
+            + codec_latency
             + match access {
-                Access::Read(_) => dev_stats.kind.sample(&DeviceAccessParams::read()),
-                Access::Write(_) => dev_stats.kind.sample(&DeviceAccessParams::write()),
+                Access::Read(_) => dev_stats
+                    .kind
+                    .sample(&DeviceAccessParams::read(), total_queue_len),
+                Access::Write(_) => dev_stats
+                    .kind
+                    .sample(&DeviceAccessParams::write(), total_queue_len),
+                Access::Discard(_) => dev_stats
+                    .kind
+                    .sample(&DeviceAccessParams::discard(), total_queue_len),
             };
-        // If nothing was submitted the device was idling
-        if dev_stats.reserved_until < now {
-            dev_stats.idle_time += now.duration_since(dev_stats.reserved_until).unwrap();
+        let queue = &mut dev_stats.queues[queue_idx];
+        // If nothing was submitted on this queue it was idling
+        if queue.reserved_until < now {
+            queue.idle_time += now.duration_since(queue.reserved_until).unwrap();
         }
-        dev_stats.reserved_until = dev_stats.reserved_until.max(until);
-        dev_stats.current_queue_len += 1;
-        if dev_stats.current_queue_len >= dev_stats.max_queue_len {
-            dev_stats.can_requeue_at = until;
+        queue.reserved_until = queue.reserved_until.max(until);
+        queue.current_queue_len += 1;
+        if queue.current_queue_len >= queue.max_queue_len {
+            queue.can_requeue_at = until;
         }
         // NOTE: Use for passed time since original queue attempt
-        dev_stats.max_q = dev_stats.max_q.max(until.duration_since(origin).unwrap());
-        dev_stats.total_q += until.duration_since(origin).unwrap();
+        queue.max_q = queue.max_q.max(until.duration_since(origin).unwrap());
+        queue.total_q += until.duration_since(origin).unwrap();
         // NOTE: Use for *only* IO duration excluding blocking queue.
-        // dev_stats.max_q = dev_stats.max_q.max(until.duration_since(now).unwrap());
-        // dev_stats.total_q += until.duration_since(now).unwrap();
-        dev_stats.total_req += 1;
+        // queue.max_q = queue.max_q.max(until.duration_since(now).unwrap());
+        // queue.total_q += until.duration_since(now).unwrap();
+        queue.total_req += 1;
+        dev_stats.in_flight.insert(*access.block(), queue_idx);
 
         Ok(match (access, is_part_of_migration) {
             (Access::Read(b), None) => Box::new(
@@ -204,16 +413,92 @@ impl<S> StorageStack<S> {
                     .into_iter(),
                 )
             }
+            // A block being migrated is never concurrently discarded.
+            (Access::Discard(_), _) => Box::new(
+                [(until, Event::Storage(StorageMsg::Finish(access.clone())))].into_iter(),
+            ),
         })
     }
 
     pub fn insert(&mut self, block: Block, device: DiskId) -> Option<Block> {
         let dev = self.devices.get_mut(&device).unwrap();
-        if dev.free > 0 {
-            dev.free = dev.free.saturating_sub(1);
+        let footprint = dev.codec_footprint();
+        if dev.free >= footprint {
+            dev.free -= footprint;
             self.blocks.insert(block, device);
             return None;
         }
         Some(block)
     }
 }
+
+/// Borrowed view over everything in a [`StorageStack`] that needs to survive a
+/// checkpoint, so `snapshot` doesn't have to clone the live state.
+#[derive(Serialize)]
+struct SnapshotRef<'a, S> {
+    blocks: &'a HashMap<Block, DiskId>,
+    devices: &'a HashMap<DiskId, DeviceState>,
+    cache: &'a CacheLogic,
+    state: &'a S,
+    blocks_on_hold: &'a HashMap<Block, SystemTime>,
+}
+
+#[derive(Deserialize)]
+struct SnapshotOwned<S> {
+    blocks: HashMap<Block, DiskId>,
+    devices: HashMap<DiskId, DeviceState>,
+    cache: CacheLogic,
+    state: S,
+    blocks_on_hold: HashMap<Block, SystemTime>,
+}
+
+impl<S: Serialize> StorageStack<S> {
+    /// Serialize the entire storage stack (block placement, per-device queue
+    /// and metric state, held-for-migration blocks, cache contents, and the
+    /// policy's own `state`) to `path`, so a run can be paused and resumed or
+    /// branched into several policies from the same point.
+    pub fn snapshot(&self, path: impl AsRef<Path>) -> Result<(), SimError> {
+        let file = std::fs::File::create(path)?;
+        let snap = SnapshotRef {
+            blocks: &self.blocks,
+            devices: &self.devices,
+            cache: &self.cache,
+            state: &self.state,
+            blocks_on_hold: &self.blocks_on_hold,
+        };
+        bincode::serialize_into(BufWriter::new(file), &snap)?;
+        Ok(())
+    }
+}
+
+impl<S> StorageStack<S>
+where
+    S: for<'de> Deserialize<'de>,
+{
+    /// Reconstruct a previously [`snapshot`](Self::snapshot)ed storage stack.
+    /// `loaded_devices` is the same CSV-backed latency table map used at
+    /// startup. A device whose `name` is a key of `loaded_devices` gets its
+    /// latency table replaced from there; every other device keeps the table
+    /// stored in the checkpoint file. The lookup is by the device's own name,
+    /// not by the latency-table name its config `kind` referred to.
+    pub fn restore(
+        path: impl AsRef<Path>,
+        loaded_devices: &HashMap<String, DeviceLatencyTable>,
+    ) -> Result<Self, SimError> {
+        let file = std::fs::File::open(path)?;
+        let mut snap: SnapshotOwned<S> = bincode::deserialize_from(BufReader::new(file))?;
+        for dev in snap.devices.values_mut() {
+            if let Ok(kind) = to_device(&dev.name, loaded_devices, dev.total) {
+                dev.kind = kind;
+            }
+        }
+        Ok(StorageStack {
+            blocks: snap.blocks,
+            devices: snap.devices,
+            cache: snap.cache,
+            state: snap.state,
+            blocks_on_hold: snap.blocks_on_hold,
+            subscriptions: SubscriptionManager::default(),
+        })
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+
+use super::{DeviceState, DiskId};
+
+/// A device-level condition a [`crate::placement::PlacementPolicy`] can
+/// subscribe to, delivered as [`crate::placement::PlacementMsg::DeviceEvent`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeviceEventKind {
+    /// The device's used fraction (`1 - free/total`) has risen to or above
+    /// the subscribed ratio.
+    CapacityAbove(f64),
+    /// The used fraction has fallen back below the subscribed ratio, having
+    /// previously crossed it.
+    CapacityBelow(f64),
+}
+
+/// One registered capacity threshold, edge-triggered: `armed` remembers
+/// whether it's currently above its ratio, so repeated checks while it stays
+/// crossed don't refire it.
+struct Threshold {
+    ratio: f64,
+    armed: bool,
+}
+
+/// Lets a [`crate::placement::PlacementPolicy`] register interest in
+/// device-level capacity thresholds during `init()`, instead of re-scanning
+/// every device on every `update()` call. `StorageStack` re-checks a
+/// device's registered thresholds whenever one of its own mutations changes
+/// that device's occupancy, emitting `PlacementMsg::DeviceEvent` only on the
+/// crossings a policy actually asked about.
+#[derive(Default)]
+pub struct SubscriptionManager {
+    thresholds: HashMap<DiskId, Vec<Threshold>>,
+}
+
+impl SubscriptionManager {
+    /// Register interest in `device`'s used fraction crossing `ratio` (e.g.
+    /// `0.9` for "90% full").
+    pub fn subscribe(&mut self, device: DiskId, ratio: f64) {
+        self.thresholds
+            .entry(device)
+            .or_default()
+            .push(Threshold {
+                ratio,
+                armed: false,
+            });
+    }
+
+    /// Re-check `device`'s registered thresholds against its current
+    /// occupancy, returning the ones that just crossed.
+    pub fn check(&mut self, device: DiskId, state: &DeviceState) -> Vec<DeviceEventKind> {
+        let Some(thresholds) = self.thresholds.get_mut(&device) else {
+            return Vec::new();
+        };
+        let used_ratio = 1.0 - state.free as f64 / state.total.max(1) as f64;
+        let mut fired = Vec::new();
+        for t in thresholds.iter_mut() {
+            if !t.armed && used_ratio >= t.ratio {
+                t.armed = true;
+                fired.push(DeviceEventKind::CapacityAbove(t.ratio));
+            } else if t.armed && used_ratio < t.ratio {
+                t.armed = false;
+                fired.push(DeviceEventKind::CapacityBelow(t.ratio));
+            }
+        }
+        fired
+    }
+}